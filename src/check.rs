@@ -0,0 +1,207 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::{
+    config::CONFIG,
+    database,
+    download::crate_file_path,
+    index::{self, IndexCrate, error::WalkIndexError},
+    rebuild,
+};
+
+/// Checks index, database and stored crate files against each other and prints all problems found.
+/// With `repair`, problems that can be fixed without losing information are fixed.
+pub(crate) fn run(repair: bool) -> Result<(), Box<dyn Error>> {
+    println!("Checking registry consistency...");
+    let index_entries: Vec<_> = index::walk_index_crates().collect();
+    let database_rows = database::get_all_versions()?;
+    let stored_files = stored_crate_files(Path::new(&CONFIG.download.path));
+    let problems = find_problems(index_entries, database_rows, stored_files);
+
+    let mut unresolved = 0;
+    for problem in &problems {
+        if repair && problem.is_repairable() {
+            match problem.repair() {
+                Ok(()) => println!("REPAIRED {problem}"),
+                Err(e) => {
+                    println!("FAILED TO REPAIR {problem}: {e}");
+                    unresolved += 1;
+                }
+            }
+        } else {
+            println!("{problem}");
+            unresolved += 1;
+        }
+    }
+    println!("Found {} problem(s), {unresolved} unresolved", problems.len());
+    if unresolved > 0 {
+        return Err(format!("{unresolved} unresolved consistency problem(s)").into());
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Problem {
+    UnparsableIndexLine { file: PathBuf, line: usize },
+    UnreadableIndexFile { file: PathBuf },
+    MissingCrateFile { name: String, vers: String },
+    UnreadableCrateFile { path: PathBuf },
+    ChecksumMismatch { name: String, vers: String, expected: String, actual: String },
+    MissingDatabaseRow { name: String, vers: String },
+    OrphanDatabaseRow { name: String, vers: String },
+    OrphanCrateFile { path: PathBuf },
+}
+
+impl Problem {
+    /// Only missing database rows are restored from the index and crate files. Orphans may hold
+    /// the only copy of descriptions, licenses or yank reasons, so they need a human decision
+    fn is_repairable(&self) -> bool {
+        matches!(self, Self::MissingDatabaseRow { .. })
+    }
+
+    fn repair(&self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::MissingDatabaseRow { name, vers } => rebuild::restore_version(name, vers),
+            _ => Err("cannot be repaired automatically".into()),
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::UnparsableIndexLine { file, line } => write!(f, "Unparsable index line {line} in {}", file.display()),
+            Self::UnreadableIndexFile { file } => write!(f, "Unreadable index file {}", file.display()),
+            Self::MissingCrateFile { name, vers } => write!(f, "Crate file missing for {name} v{vers}"),
+            Self::UnreadableCrateFile { path } => write!(f, "Unreadable crate file {}", path.display()),
+            Self::ChecksumMismatch { name, vers, expected, actual } =>
+                write!(f, "Checksum mismatch for {name} v{vers}: index has {expected}, file has {actual}"),
+            Self::MissingDatabaseRow { name, vers } => write!(f, "Database row missing for {name} v{vers}"),
+            Self::OrphanDatabaseRow { name, vers } => write!(f, "Database row for {name} v{vers} not in index"),
+            Self::OrphanCrateFile { path } => write!(f, "Crate file {} not in index", path.display()),
+        }
+    }
+}
+
+/// Maps every file in the storage directory to its SHA-256 checksum, or `None` if it is not readable
+fn stored_crate_files(storage: &Path) -> HashMap<PathBuf, Option<String>> {
+    WalkDir::new(storage).into_iter().flatten()
+        .filter(|e| e.file_type().is_file())
+        .map(|e| {
+            let checksum = std::fs::read(e.path()).ok().map(sha256::digest);
+            (e.into_path(), checksum)
+        })
+        .collect()
+}
+
+pub(crate) fn find_problems(
+    index_entries: Vec<Result<IndexCrate, WalkIndexError>>,
+    database_rows: Vec<(String, String)>,
+    mut stored_files: HashMap<PathBuf, Option<String>>,
+) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut database_rows: HashSet<(String, String)> = database_rows.into_iter()
+        .map(|(name, vers)| (name.to_lowercase(), vers))
+        .collect();
+
+    for entry in index_entries {
+        let index_crate = match entry {
+            Ok(c) => c,
+            Err(WalkIndexError::ParseJson(_, file, line)) => {
+                problems.push(Problem::UnparsableIndexLine { file, line });
+                continue;
+            },
+            Err(WalkIndexError::IoError(_, file)) => {
+                problems.push(Problem::UnreadableIndexFile { file });
+                continue;
+            }
+        };
+        let IndexCrate { name, vers, cksum, .. } = index_crate;
+
+        let path = crate_file_path(&name, &vers);
+        match stored_files.remove(&path) {
+            None => problems.push(Problem::MissingCrateFile { name: name.clone(), vers: vers.clone() }),
+            Some(None) => problems.push(Problem::UnreadableCrateFile { path }),
+            Some(Some(actual)) if actual != cksum => problems.push(Problem::ChecksumMismatch {
+                name: name.clone(), vers: vers.clone(), expected: cksum, actual
+            }),
+            Some(Some(_)) => {}
+        }
+
+        if !database_rows.remove(&(name.to_lowercase(), vers.clone())) {
+            problems.push(Problem::MissingDatabaseRow { name, vers });
+        }
+    }
+
+    let mut orphan_rows: Vec<_> = database_rows.into_iter().collect();
+    orphan_rows.sort();
+    problems.extend(orphan_rows.into_iter().map(|(name, vers)| Problem::OrphanDatabaseRow { name, vers }));
+
+    let mut orphan_files: Vec<_> = stored_files.into_keys().collect();
+    orphan_files.sort();
+    problems.extend(orphan_files.into_iter().map(|path| Problem::OrphanCrateFile { path }));
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+    use super::{find_problems, Problem};
+    use crate::{index::IndexCrate, download::crate_file_path};
+
+    fn index_crate(name: &str, vers: &str, cksum: &str) -> IndexCrate {
+        IndexCrate { name: name.to_string(), vers: vers.to_string(), cksum: cksum.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn consistent_registry_has_no_problems() {
+        let files = HashMap::from([(crate_file_path("serde", "1.0.0"), Some("abc".to_string()))]);
+        let problems = find_problems(
+            vec![Ok(index_crate("serde", "1.0.0", "abc"))],
+            vec![("serde".to_string(), "1.0.0".to_string())],
+            files);
+        assert_eq!(problems, vec![]);
+    }
+
+    #[test]
+    fn detects_checksum_mismatch_and_missing_row() {
+        let files = HashMap::from([(crate_file_path("serde", "1.0.0"), Some("def".to_string()))]);
+        let problems = find_problems(vec![Ok(index_crate("serde", "1.0.0", "abc"))], vec![], files);
+        assert_eq!(problems, vec![
+            Problem::ChecksumMismatch {
+                name: "serde".to_string(), vers: "1.0.0".to_string(),
+                expected: "abc".to_string(), actual: "def".to_string()
+            },
+            Problem::MissingDatabaseRow { name: "serde".to_string(), vers: "1.0.0".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn detects_orphans() {
+        let orphan = PathBuf::from("leftover.crate");
+        let problems = find_problems(
+            vec![Ok(index_crate("serde", "1.0.0", "abc"))],
+            vec![("serde".to_string(), "1.0.0".to_string()), ("serde".to_string(), "1.0.1".to_string())],
+            HashMap::from([(orphan.clone(), None)]));
+        assert_eq!(problems, vec![
+            Problem::MissingCrateFile { name: "serde".to_string(), vers: "1.0.0".to_string() },
+            Problem::OrphanDatabaseRow { name: "serde".to_string(), vers: "1.0.1".to_string() },
+            Problem::OrphanCrateFile { path: orphan },
+        ]);
+    }
+
+    #[test]
+    fn only_missing_rows_are_repaired() {
+        let missing = Problem::MissingDatabaseRow { name: "serde".to_string(), vers: "1.0.0".to_string() };
+        let orphan_row = Problem::OrphanDatabaseRow { name: "serde".to_string(), vers: "1.0.1".to_string() };
+        let orphan_file = Problem::OrphanCrateFile { path: PathBuf::from("leftover.crate") };
+        assert!(missing.is_repairable());
+        assert!(!orphan_row.is_repairable() && !orphan_file.is_repairable());
+    }
+}
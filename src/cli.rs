@@ -0,0 +1,120 @@
+use std::{path::PathBuf, sync::LazyLock};
+
+pub(crate) const USAGE: &str = "\
+Usage: cargo_registry_server [CONFIG] [COMMAND]
+
+Commands:
+    serve               Run the registry server (default)
+    check [--repair]    Check index, database and crate files for consistency
+    rebuild             Restore missing database rows from the index and crate files
+    migrate-index       Rewrite all index files in canonical form
+    export FILE [--with-git]
+                        Write index, database and crate files into one archive
+    import FILE         Replace index, database and crate files with an exported archive
+    index-at CRATE TIMESTAMP
+                        Print the index file of a crate as it was at the given time";
+
+pub static ARGUMENTS: LazyLock<Arguments> = LazyLock::new(|| {
+    match std::env::args().skip(1).collect::<Vec<_>>().try_into() {
+        Ok(arguments) => arguments,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2)
+        }
+    }
+});
+
+#[derive(Debug, PartialEq)]
+pub struct Arguments {
+    pub config_path: Option<PathBuf>,
+    pub command: Command,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Serve,
+    Check { repair: bool },
+    Rebuild,
+    MigrateIndex,
+    Export { path: PathBuf, with_git: bool },
+    Import { path: PathBuf },
+    IndexAt { crate_name: String, timestamp: String },
+}
+
+impl TryFrom<Vec<String>> for Arguments {
+    type Error = String;
+
+    fn try_from(value: Vec<String>) -> Result<Self, Self::Error> {
+        let mut args = value.into_iter().peekable();
+        // Everything that is not a command in first place is the configuration file
+        let config_path = args.next_if(|a| !is_command(a)).map(PathBuf::from);
+        let command = match args.next().as_deref() {
+            None | Some("serve") => Command::Serve,
+            Some("check") => match args.next().as_deref() {
+                None => Command::Check { repair: false },
+                Some("--repair") => Command::Check { repair: true },
+                Some(other) => return Err(format!("Unknown option for check: {other}")),
+            },
+            Some("rebuild") => Command::Rebuild,
+            Some("migrate-index") => Command::MigrateIndex,
+            Some("export") => {
+                let path = args.next().ok_or("Missing archive path for export")?.into();
+                let with_git = args.next_if(|a| a == "--with-git").is_some();
+                Command::Export { path, with_git }
+            },
+            Some("index-at") => match (args.next(), args.next()) {
+                (Some(crate_name), Some(timestamp)) => Command::IndexAt { crate_name, timestamp },
+                _ => return Err("index-at needs a crate name and a timestamp".to_string()),
+            },
+            Some("import") => Command::Import { path: args.next().ok_or("Missing archive path for import")?.into() },
+            Some(other) => return Err(format!("Unknown command: {other}")),
+        };
+        if let Some(extra) = args.next() {
+            return Err(format!("Unexpected argument: {extra}"));
+        }
+        Ok(Self { config_path, command })
+    }
+}
+
+fn is_command(arg: &str) -> bool {
+    matches!(arg, "serve" | "check" | "rebuild" | "migrate-index" | "export" | "import" | "index-at")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::{Arguments, Command};
+
+    fn parse(args: &[&str]) -> Result<Arguments, String> {
+        args.iter().map(ToString::to_string).collect::<Vec<_>>().try_into()
+    }
+
+    #[test]
+    fn no_arguments_serves_default() {
+        assert_eq!(parse(&[]).unwrap(), Arguments { config_path: None, command: Command::Serve });
+    }
+
+    #[test]
+    fn config_path_only() {
+        assert_eq!(parse(&["registry.toml"]).unwrap(),
+            Arguments { config_path: Some(PathBuf::from("registry.toml")), command: Command::Serve });
+    }
+
+    #[test]
+    fn check_with_config() {
+        assert_eq!(parse(&["registry.toml", "check", "--repair"]).unwrap(),
+            Arguments { config_path: Some(PathBuf::from("registry.toml")), command: Command::Check { repair: true } });
+    }
+
+    #[test]
+    fn export_with_git() {
+        assert_eq!(parse(&["export", "registry.tar.gz", "--with-git"]).unwrap().command,
+            Command::Export { path: PathBuf::from("registry.tar.gz"), with_git: true });
+        assert!(parse(&["import"]).is_err());
+    }
+
+    #[test]
+    fn unknown_option_fails() {
+        assert!(parse(&["check", "--force"]).is_err());
+    }
+}
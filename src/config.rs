@@ -1,16 +1,16 @@
-use std::{net::{IpAddr, SocketAddr, Ipv4Addr}, path::PathBuf};
+use std::{net::{IpAddr, SocketAddr, Ipv4Addr}, path::PathBuf, sync::LazyLock, collections::HashMap};
 
-use once_cell::sync::Lazy;
-use serde::{Deserialize, Serialize, Serializer};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
 use url::{Url, ParseError};
 
-pub static CONFIG: Lazy<Config> = Lazy::new(|| {
-    let args = std::env::args().collect::<Vec<_>>();
-    let Some(arg_path) = args.get(1) else {
+use crate::{cli::ARGUMENTS, dependency::ValidRegistry};
+
+pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
+    let Some(arg_path) = &ARGUMENTS.config_path else {
         println!("Using default configuration");
         return Config::default();
     };
-    let arg_path = PathBuf::from(arg_path);
     println!("Reading config at {}", arg_path.display());
     let s = std::fs::read_to_string(arg_path).expect("Reading config file failed. Does it exist?");
     toml::from_str(&s).expect("Invalid configuration TOML")
@@ -23,6 +23,17 @@ pub struct Config {
     pub download: DownloadConfig,
     pub net: NetConfig,
     pub database: DatabaseConfig,
+    #[serde(default)]
+    pub publish: PublishConfig,
+    #[serde(default)]
+    pub names: NamePolicyConfig,
+    #[serde(default)]
+    pub dependencies: DependencyPolicyConfig,
+    #[serde(default)]
+    pub licenses: LicensePolicyConfig,
+    /// Authorization token to the name of its user, recorded instead of the token where actions are logged
+    #[serde(default)]
+    pub users: HashMap<String, String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -36,7 +47,7 @@ pub struct NetConfig {
 impl Default for NetConfig {
     fn default() -> Self {
         Self {
-            ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
             port: 7878,
             threads: Some(1)
         }
@@ -75,16 +86,191 @@ impl Default for DatabaseConfig {
 #[allow(clippy::module_name_repetitions)]
 pub struct IndexConfig {
     pub path: PathBuf,
+    /// Git remotes (names, URLs or paths) every index commit gets pushed to
+    #[serde(default)]
+    pub remotes: Vec<String>,
+    /// The index URL clients use for this registry, in git or sparse form
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// Index URLs this registry had before, dependencies naming them are rewritten by `migrate-index`
+    #[serde(default)]
+    pub previous_urls: Vec<String>,
 }
 
 impl Default for IndexConfig {
     fn default() -> Self {
         Self {
-            path: PathBuf::from("target/debug/index")
+            path: PathBuf::from("target/debug/index"),
+            remotes: vec![],
+            public_url: None,
+            previous_urls: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct PublishConfig {
+    /// Category slugs published crates may use, the crates.io categories by default
+    pub categories: Vec<String>,
+    /// Badge name to attribute name to accepted values, where an empty list accepts any value
+    pub badges: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+impl Default for PublishConfig {
+    fn default() -> Self {
+        let maintenance_status = ["actively-developed", "passively-maintained", "as-is", "experimental",
+            "looking-for-maintainer", "deprecated", "none"];
+        Self {
+            categories: CRATES_IO_CATEGORIES.iter().map(ToString::to_string).collect(),
+            badges: HashMap::from([(
+                "maintenance".to_string(),
+                HashMap::from([("status".to_string(), maintenance_status.iter().map(ToString::to_string).collect())]),
+            )]),
+        }
+    }
+}
+
+/// Where dependencies of published crates may come from
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DependencyPolicyConfig {
+    /// Index URLs of other registries dependencies may come from, in git or sparse form, crates.io by default
+    pub allowed_registries: Vec<ValidRegistry>,
+    pub mode: DependencyPolicyMode,
+}
+
+impl Default for DependencyPolicyConfig {
+    fn default() -> Self {
+        Self {
+            allowed_registries: vec![ValidRegistry::CratesIO],
+            mode: DependencyPolicyMode::Deny,
         }
     }
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyPolicyMode {
+    /// Publishing fails for every dependency violating the policy
+    Deny,
+    /// Publishing succeeds and cargo shows the violations
+    Warn,
+}
+
+/// Licenses published crates may use. Entries are SPDX license ids,
+/// also covering the ids they are a dash separated prefix of
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LicensePolicyConfig {
+    /// If not empty, only these licenses are accepted
+    pub allowed: Vec<String>,
+    pub denied: Vec<String>,
+    pub mode: LicensePolicyMode,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LicensePolicyMode {
+    /// Publishing fails for licenses the policy does not accept
+    #[default]
+    Deny,
+    /// Publishing succeeds and cargo shows the licenses the policy does not accept
+    Warn,
+}
+
+/// Rules for the names of new crates, on top of the fixed ones in `name_policy`
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct NamePolicyConfig {
+    /// Names nobody can publish, compared ignoring case and dash/underscore differences
+    pub reserved: Vec<String>,
+    /// Regular expressions, new crates with a name matching any of them are rejected
+    #[serde(deserialize_with = "regexes")]
+    pub blocklist: Vec<Regex>,
+    /// Team name to the authorization tokens of its members
+    pub teams: HashMap<String, Vec<String>>,
+    /// Name prefix like `payments-` to the team that may publish new crates starting with it
+    pub prefixes: HashMap<String, String>,
+    pub public: PublicNamesConfig,
+    pub typosquatting: TyposquattingConfig,
+}
+
+/// How new crates with names confusingly close to existing ones are treated
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TyposquattingConfig {
+    pub mode: TyposquattingMode,
+    /// Largest number of inserted, removed, replaced or swapped characters still considered a typo
+    pub max_distance: usize,
+}
+
+impl Default for TyposquattingConfig {
+    fn default() -> Self {
+        Self { mode: TyposquattingMode::Warn, max_distance: 1 }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TyposquattingMode {
+    Off,
+    /// Publishing succeeds and cargo shows the similar names
+    Warn,
+    Block,
+}
+
+/// Names of crates on crates.io that new crates must not take over,
+/// so dependents resolving from both registries cannot get the wrong one
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PublicNamesConfig {
+    /// A file with one crate name per line or a checkout of the crates.io index, nothing is checked without it
+    pub source: Option<PathBuf>,
+    pub mode: PublicNameMode,
+    /// Names that may be published here although they exist publicly, only used in `override` mode
+    pub overrides: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PublicNameMode {
+    /// Public names are never published here
+    #[default]
+    Deny,
+    /// Public names are only published here if listed in `overrides`
+    Override,
+}
+
+fn regexes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(D::Error::custom))
+        .collect()
+}
+
+const CRATES_IO_CATEGORIES: &[&str] = &[
+    "accessibility", "aerospace", "aerospace::drones", "aerospace::protocols", "aerospace::simulation",
+    "aerospace::space-protocols", "aerospace::unmanned-aerial-vehicles", "algorithms", "api-bindings",
+    "asynchronous", "authentication", "caching", "command-line-interface", "command-line-utilities",
+    "compilers", "compression", "computer-vision", "concurrency", "config", "cryptography",
+    "cryptography::cryptocurrencies", "data-structures", "database", "database-implementations",
+    "date-and-time", "development-tools", "development-tools::build-utils", "development-tools::cargo-plugins",
+    "development-tools::debugging", "development-tools::ffi", "development-tools::procedural-macro-helpers",
+    "development-tools::profiling", "development-tools::testing", "email", "embedded", "emulators", "encoding",
+    "external-ffi-bindings", "filesystem", "finance", "game-development", "game-engines", "games", "graphics",
+    "gui", "hardware-support", "internationalization", "localization", "mathematics", "memory-management",
+    "multimedia", "multimedia::audio", "multimedia::encoding", "multimedia::images", "multimedia::video",
+    "network-programming", "no-std", "no-std::no-alloc", "os", "os::android-apis", "os::freebsd-apis",
+    "os::linux-apis", "os::macos-apis", "os::unix-apis", "os::windows-apis", "parser-implementations",
+    "parsing", "rendering", "rendering::data-formats", "rendering::engine", "rendering::graphics-api",
+    "rust-patterns", "science", "science::bioinformatics", "science::geo", "science::neuroscience",
+    "science::robotics", "simulation", "template-engine", "text-editors", "text-processing",
+    "value-formatting", "virtualization", "visualization", "wasm", "web-programming",
+    "web-programming::http-client", "web-programming::http-server", "web-programming::websocket",
+];
+
 #[derive(Debug, Serialize)]
 pub struct IndexConfigFile {
     dl: Url,
@@ -0,0 +1,249 @@
+use std::{
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use tar::{Archive, EntryType};
+
+use self::error::CrateFileError;
+
+pub(crate) mod error;
+
+/// Largest accepted total size of all files in a .crate file, the same limit crates.io uses
+const MAX_UNPACKED_SIZE: u64 = 512 * 1024 * 1024;
+
+/// The parts of a normalized `Cargo.toml` inside a published .crate file the registry cares about
+#[derive(Deserialize, Debug)]
+pub(crate) struct Manifest {
+    pub(crate) package: ManifestPackage,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct ManifestPackage {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    #[serde(default)]
+    pub(crate) authors: Vec<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) documentation: Option<String>,
+    pub(crate) homepage: Option<String>,
+    /// Either a path relative to the package root or a boolean
+    pub(crate) readme: Option<toml::Value>,
+    #[serde(default)]
+    pub(crate) keywords: Vec<String>,
+    #[serde(default)]
+    pub(crate) categories: Vec<String>,
+    pub(crate) license: Option<String>,
+    pub(crate) license_file: Option<String>,
+    pub(crate) repository: Option<String>,
+    pub(crate) rust_version: Option<String>,
+}
+
+impl ManifestPackage {
+    pub(crate) fn readme_path(&self) -> Option<&str> {
+        self.readme.as_ref().and_then(toml::Value::as_str)
+    }
+}
+
+/// Reads `name-vers/Cargo.toml` out of a gzipped .crate file
+pub(crate) fn read_manifest(crate_file: &[u8], name: &str, vers: &str) -> Result<Manifest, CrateFileError> {
+    let manifest = read_file(crate_file, name, vers, Path::new("Cargo.toml"))?
+        .ok_or(CrateFileError::MissingManifest)?;
+    let manifest = String::from_utf8_lossy(&manifest);
+    Ok(toml::from_str(&manifest)?)
+}
+
+/// Checks that every entry of a gzipped .crate file stays inside its `name-vers` root directory
+/// and that `name-vers/Cargo.toml` exists and describes exactly this name and version.
+/// Returns that manifest.
+pub(crate) fn validate(crate_file: &[u8], name: &str, vers: &str) -> Result<Manifest, CrateFileError> {
+    let root = format!("{name}-{vers}");
+    let mut unpacked_size = 0;
+    let mut manifest = None;
+    let mut archive = Archive::new(GzDecoder::new(crate_file));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let display = path.display().to_string();
+        let mut components = path.components();
+        match components.next() {
+            Some(Component::Normal(first)) if first.to_string_lossy().eq_ignore_ascii_case(&root) => {},
+            _ => return Err(CrateFileError::OutsideRoot(display)),
+        }
+        let relative = components.as_path();
+        if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(CrateFileError::UnsafePath(display));
+        }
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Continuous => {},
+            EntryType::Directory => continue,
+            kind @ (EntryType::Symlink | EntryType::Link) => {
+                let target = entry.link_name()?.ok_or_else(|| CrateFileError::UnsafeLink(display.clone()))?;
+                // Hard link targets are archive paths, symbolic link targets are relative to the link
+                let stays_inside = if kind == EntryType::Link {
+                    let mut target = target.components();
+                    matches!(target.next(), Some(Component::Normal(first)) if first.to_string_lossy().eq_ignore_ascii_case(&root))
+                        && stays_below(Path::new(""), target.as_path())
+                } else {
+                    stays_below(relative.parent().unwrap_or(Path::new("")), &target)
+                };
+                if !stays_inside {
+                    return Err(CrateFileError::UnsafeLink(display));
+                }
+                continue;
+            },
+            _ => return Err(CrateFileError::UnsupportedEntry(display)),
+        }
+        unpacked_size += entry.size();
+        if unpacked_size > MAX_UNPACKED_SIZE {
+            return Err(CrateFileError::TooLarge(MAX_UNPACKED_SIZE));
+        }
+        if relative == Path::new("Cargo.toml") {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            manifest = Some(toml::from_str::<Manifest>(&content)?);
+        }
+    }
+    let manifest = manifest.ok_or(CrateFileError::MissingManifest)?;
+    if !manifest.package.name.eq_ignore_ascii_case(name) {
+        return Err(CrateFileError::NameMismatch(manifest.package.name));
+    }
+    if manifest.package.version != vers {
+        return Err(CrateFileError::VersionMismatch(manifest.package.version));
+    }
+    Ok(manifest)
+}
+
+/// Whether `target`, resolved relative to the directory `base` below the root, stays below the root
+fn stays_below(base: &Path, target: &Path) -> bool {
+    let mut depth = base.components().count();
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {},
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// Reads the file at `relative_path` below the `name-vers` root directory of a gzipped .crate file.
+/// The root directory is matched case insensitively, as crate names are stored lowercase.
+pub(crate) fn read_file(crate_file: &[u8], name: &str, vers: &str, relative_path: &Path) -> Result<Option<Vec<u8>>, CrateFileError> {
+    let root = format!("{name}-{vers}");
+    let wanted: PathBuf = relative_path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect();
+    let mut archive = Archive::new(GzDecoder::new(crate_file));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let mut components = path.components();
+        let Some(Component::Normal(first)) = components.next() else {
+            continue;
+        };
+        if first.to_string_lossy().eq_ignore_ascii_case(&root) && components.as_path() == wanted {
+            let mut content = vec![];
+            entry.read_to_end(&mut content)?;
+            return Ok(Some(content));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::Path;
+    use flate2::{write::GzEncoder, Compression};
+    use super::{read_manifest, read_file, validate};
+    use crate::crate_file::error::CrateFileError;
+
+    /// Builds a gzipped tarball containing regular files at the given paths
+    pub(crate) fn build_crate_file(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn build_crate_file_with_link(kind: tar::EntryType, path: &str, target: &str) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(MANIFEST.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "Serde-1.0.0/Cargo.toml", MANIFEST).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(0);
+        builder.append_link(&mut header, path, target).unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    const MANIFEST: &[u8] = br#"
+[package]
+name = "Serde"
+version = "1.0.0"
+description = "A serialization framework"
+readme = "README.md"
+license = "MIT"
+"#;
+
+    #[test]
+    fn reads_manifest_case_insensitive() {
+        let file = build_crate_file(&[("Serde-1.0.0/Cargo.toml", MANIFEST)]);
+        let manifest = read_manifest(&file, "serde", "1.0.0").unwrap();
+        assert_eq!(manifest.package.description.as_deref(), Some("A serialization framework"));
+        assert_eq!(manifest.package.readme_path(), Some("README.md"));
+    }
+
+    #[test]
+    fn reads_readme() {
+        let file = build_crate_file(&[("serde-1.0.0/Cargo.toml", MANIFEST), ("serde-1.0.0/README.md", b"# Serde")]);
+        let readme = read_file(&file, "serde", "1.0.0", Path::new("./README.md")).unwrap();
+        assert_eq!(readme.as_deref(), Some(&b"# Serde"[..]));
+    }
+
+    #[test]
+    fn missing_manifest() {
+        let file = build_crate_file(&[("serde-1.0.1/Cargo.toml", MANIFEST)]);
+        assert!(matches!(read_manifest(&file, "serde", "1.0.0"), Err(CrateFileError::MissingManifest)));
+    }
+
+    #[test]
+    fn validate_accepts_matching_manifest() {
+        let file = build_crate_file(&[("Serde-1.0.0/Cargo.toml", MANIFEST), ("Serde-1.0.0/src/lib.rs", b"")]);
+        assert_eq!(validate(&file, "serde", "1.0.0").unwrap().package.name, "Serde");
+    }
+
+    #[test]
+    fn validate_rejects_mismatching_manifest() {
+        let file = build_crate_file(&[("serde-1.0.0/Cargo.toml", MANIFEST)]);
+        assert!(matches!(validate(&file, "serde", "1.0.1"), Err(CrateFileError::OutsideRoot(_))));
+        let file = build_crate_file(&[("serde-1.0.1/Cargo.toml", MANIFEST)]);
+        assert!(matches!(validate(&file, "serde", "1.0.1"), Err(CrateFileError::VersionMismatch(v)) if v == "1.0.0"));
+        let file = build_crate_file(&[("serde-1.0.0/src/lib.rs", b"")]);
+        assert!(matches!(validate(&file, "serde", "1.0.0"), Err(CrateFileError::MissingManifest)));
+    }
+
+    #[test]
+    fn validate_rejects_escaping_entries() {
+        let file = build_crate_file(&[("Serde-1.0.0/Cargo.toml", MANIFEST), ("other/lib.rs", b"")]);
+        assert!(matches!(validate(&file, "serde", "1.0.0"), Err(CrateFileError::OutsideRoot(_))));
+        let file = build_crate_file_with_link(tar::EntryType::Symlink, "Serde-1.0.0/src/passwd", "../../../etc/passwd");
+        assert!(matches!(validate(&file, "serde", "1.0.0"), Err(CrateFileError::UnsafeLink(_))));
+        let file = build_crate_file_with_link(tar::EntryType::Symlink, "Serde-1.0.0/src/README.md", "../README.md");
+        assert!(validate(&file, "serde", "1.0.0").is_ok());
+        let file = build_crate_file_with_link(tar::EntryType::Link, "Serde-1.0.0/src/copy", "other-1.0.0/Cargo.toml");
+        assert!(matches!(validate(&file, "serde", "1.0.0"), Err(CrateFileError::UnsafeLink(_))));
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::Error as IoError,
+};
+
+#[derive(Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub(crate) enum CrateFileError {
+    IoError(IoError),
+    MissingManifest,
+    InvalidManifest(toml::de::Error),
+    OutsideRoot(String),
+    UnsafePath(String),
+    UnsafeLink(String),
+    UnsupportedEntry(String),
+    TooLarge(u64),
+    NameMismatch(String),
+    VersionMismatch(String),
+}
+
+impl Error for CrateFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IoError(i) => Some(i),
+            Self::InvalidManifest(t) => Some(t),
+            Self::MissingManifest | Self::OutsideRoot(_) | Self::UnsafePath(_) | Self::UnsafeLink(_)
+                | Self::UnsupportedEntry(_) | Self::TooLarge(_) | Self::NameMismatch(_) | Self::VersionMismatch(_) => None,
+        }
+    }
+}
+
+impl Display for CrateFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::IoError(e) => write!(f, "failed to read crate file: {e}"),
+            Self::MissingManifest => write!(f, "crate file contains no Cargo.toml"),
+            Self::InvalidManifest(e) => write!(f, "invalid Cargo.toml in crate file: {e}"),
+            Self::OutsideRoot(p) => write!(f, "crate file entry {p} is not inside the name-version directory"),
+            Self::UnsafePath(p) => write!(f, "crate file entry {p} has an absolute or parent directory path"),
+            Self::UnsafeLink(p) => write!(f, "crate file entry {p} links outside the package"),
+            Self::UnsupportedEntry(p) => write!(f, "crate file entry {p} is neither a file, a directory nor a link"),
+            Self::TooLarge(max) => write!(f, "crate file unpacks to more than {max} bytes"),
+            Self::NameMismatch(n) => write!(f, "Cargo.toml in crate file is for package {n}"),
+            Self::VersionMismatch(v) => write!(f, "Cargo.toml in crate file is for version {v}"),
+        }
+    }
+}
+
+impl From<IoError> for CrateFileError {
+    fn from(value: IoError) -> Self {
+        Self::IoError(value)
+    }
+}
+
+impl From<toml::de::Error> for CrateFileError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::InvalidManifest(value)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{io::{Write, Result as IoResult}, net::TcpStream, collections::BTreeMap};
+
+use serde::Serialize;
+
+use crate::{
+    database, version,
+    error::ReturnJson,
+    http::{Response, Byteable},
+};
+
+/// Versions are listed in semver order, the crate's description is the one of the highest version
+pub fn show_crate(mut stream: TcpStream, crate_name: &str) -> IoResult<()> {
+    println!("CRATE INFO {crate_name}");
+    let mut versions = match database::get_version_results(Some(&crate_name.to_lowercase())) {
+        Ok(v) => v,
+        Err(e) => return stream.write_all(&Response::new(500).body(ReturnJson::new(&[e])).into_bytes()),
+    };
+    versions.sort_by(|a, b| version::compare(&a.num, &b.num));
+    let Some(latest) = versions.last() else {
+        return stream.write_all(&Response::new(404).body(ReturnJson::new(&["crate does not exist"])).into_bytes());
+    };
+    let crate_result = CrateResult {
+        name: latest.crate_name.clone(),
+        description: latest.description.clone(),
+        keywords: latest.keywords.clone(),
+        categories: latest.categories.clone(),
+    };
+    let json = serde_json::to_string(&CrateJson { crate_result, versions })?;
+    stream.write_all(&Response::new(200).body(json).into_bytes())
+}
+
+pub fn show_version(mut stream: TcpStream, crate_name: &str, version: &str) -> IoResult<()> {
+    println!("VERSION INFO {crate_name} v{version}");
+    let versions = match database::get_version_results(Some(&crate_name.to_lowercase())) {
+        Ok(v) => v,
+        Err(e) => return stream.write_all(&Response::new(500).body(ReturnJson::new(&[e])).into_bytes()),
+    };
+    let Some(version) = versions.into_iter().find(|v| v.num == version) else {
+        return stream.write_all(&Response::new(404).body(ReturnJson::new(&["version does not exist"])).into_bytes());
+    };
+    let json = serde_json::to_string(&VersionJson { version })?;
+    stream.write_all(&Response::new(200).body(json).into_bytes())
+}
+
+/// The highest version of every crate depending on `crate_name`
+pub fn show_reverse_dependencies(mut stream: TcpStream, crate_name: &str) -> IoResult<()> {
+    println!("REVERSE DEPENDENCIES {crate_name}");
+    let dependencies = match database::get_reverse_dependencies(crate_name) {
+        Ok(d) => d,
+        Err(e) => return stream.write_all(&Response::new(500).body(ReturnJson::new(&[e])).into_bytes()),
+    };
+    let dependencies = highest_version_per_crate(dependencies);
+    let json = serde_json::to_string(&ReverseDependenciesJson { meta: Meta { total: dependencies.len() }, dependencies })?;
+    stream.write_all(&Response::new(200).body(json).into_bytes())
+}
+
+/// Expects `dependencies` to be grouped by crate
+fn highest_version_per_crate(dependencies: Vec<ReverseDependency>) -> Vec<ReverseDependency> {
+    let mut highest: Vec<ReverseDependency> = vec![];
+    for dependency in dependencies {
+        match highest.last_mut() {
+            Some(last) if last.crate_name == dependency.crate_name => {
+                if version::compare(&dependency.num, &last.num).is_gt() {
+                    *last = dependency;
+                }
+            },
+            _ => highest.push(dependency),
+        }
+    }
+    highest
+}
+
+#[derive(Serialize, Debug)]
+struct CrateJson {
+    #[serde(rename = "crate")]
+    crate_result: CrateResult,
+    versions: Vec<VersionResult>,
+}
+
+#[derive(Serialize, Debug)]
+struct CrateResult {
+    name: String,
+    description: Option<String>,
+    keywords: Vec<String>,
+    categories: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct VersionJson {
+    version: VersionResult,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct VersionResult {
+    #[serde(rename = "crate")]
+    pub(crate) crate_name: String,
+    pub(crate) num: String,
+    pub(crate) description: Option<String>,
+    pub(crate) license: Option<String>,
+    /// The licenses of the SPDX expression in `license`
+    pub(crate) licenses: Vec<String>,
+    pub(crate) rust_version: Option<String>,
+    pub(crate) yanked: bool,
+    pub(crate) yank_reason: Option<String>,
+    pub(crate) yanked_by: Option<String>,
+    pub(crate) yanked_at: Option<String>,
+    pub(crate) links: Option<String>,
+    pub(crate) checksum: Option<String>,
+    pub(crate) authors: Vec<String>,
+    pub(crate) keywords: Vec<String>,
+    pub(crate) categories: Vec<String>,
+    pub(crate) features: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+struct ReverseDependenciesJson {
+    dependencies: Vec<ReverseDependency>,
+    meta: Meta,
+}
+
+#[derive(Serialize, Debug)]
+struct Meta {
+    total: usize,
+}
+
+/// A version of another crate depending on the requested one
+#[derive(Serialize, Debug)]
+pub(crate) struct ReverseDependency {
+    #[serde(rename = "crate")]
+    pub(crate) crate_name: String,
+    pub(crate) num: String,
+    pub(crate) req: String,
+    pub(crate) optional: bool,
+    pub(crate) default_features: bool,
+    pub(crate) features: Vec<String>,
+    pub(crate) target: Option<String>,
+    pub(crate) kind: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReverseDependency, highest_version_per_crate};
+
+    fn dependency(crate_name: &str, num: &str) -> ReverseDependency {
+        ReverseDependency {
+            crate_name: crate_name.to_string(), num: num.to_string(), req: "^1".to_string(), optional: false,
+            default_features: true, features: vec![], target: None, kind: "normal".to_string(),
+        }
+    }
+
+    #[test]
+    fn only_highest_dependent_versions() {
+        let dependencies = highest_version_per_crate(vec![
+            dependency("bar", "0.10.0"), dependency("bar", "0.9.0"), dependency("foo", "1.0.0-rc.1"),
+        ]);
+        let versions: Vec<_> = dependencies.iter().map(|d| (d.crate_name.as_str(), d.num.as_str())).collect();
+        assert_eq!(versions, [("bar", "0.10.0"), ("foo", "1.0.0-rc.1")]);
+    }
+}
@@ -1,20 +1,130 @@
-use std::path::Path;
-use rusqlite::{Connection, Transaction, TransactionBehavior};
+use std::{path::Path, collections::HashMap};
+use rusqlite::{Connection, Row, Transaction, TransactionBehavior, types::Type};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
 
 use crate::{
     publish::PublishedPackage, 
     config::CONFIG, 
-    owners::UserResult
+    owners::UserResult,
+    crate_info::{VersionResult, ReverseDependency},
+    yank::YankMetadata,
+    license,
 };
 
 use self::error::AddOwnerError;
 
 pub mod error;
 
+/// Schema changes made after the tables created in `init`, applied in order.
+/// `PRAGMA user_version` holds the number of migrations already applied.
+const MIGRATIONS: &[&str] = &[
+    "ALTER TABLE versions ADD COLUMN rust_version TEXT",
+    "ALTER TABLE versions ADD COLUMN yanked INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE versions ADD COLUMN yank_reason TEXT;
+    ALTER TABLE versions ADD COLUMN yanked_by TEXT;
+    ALTER TABLE versions ADD COLUMN yanked_at TEXT;",
+    "ALTER TABLE versions ADD COLUMN links TEXT;
+    ALTER TABLE versions ADD COLUMN cksum TEXT;
+    CREATE TABLE authors (
+        versionId INTEGER NOT NULL REFERENCES versions(versionId),
+        name TEXT NOT NULL
+    );
+    CREATE TABLE keywords (
+        versionId INTEGER NOT NULL REFERENCES versions(versionId),
+        keyword TEXT NOT NULL
+    );
+    CREATE TABLE categories (
+        versionId INTEGER NOT NULL REFERENCES versions(versionId),
+        category TEXT NOT NULL
+    );
+    CREATE TABLE features (
+        versionId INTEGER NOT NULL REFERENCES versions(versionId),
+        name TEXT NOT NULL,
+        enables TEXT NOT NULL
+    );
+    CREATE TABLE dependencies (
+        versionId INTEGER NOT NULL REFERENCES versions(versionId),
+        name TEXT NOT NULL,
+        version_req TEXT NOT NULL,
+        features TEXT NOT NULL,
+        optional INTEGER NOT NULL,
+        default_features INTEGER NOT NULL,
+        target TEXT,
+        kind TEXT NOT NULL,
+        registry TEXT,
+        explicit_name_in_toml TEXT
+    );
+    CREATE INDEX dependencies_by_name ON dependencies(name);
+    CREATE TABLE badges (
+        versionId INTEGER NOT NULL REFERENCES versions(versionId),
+        badge TEXT NOT NULL,
+        attribute TEXT,
+        value TEXT
+    );",
+    // Dependencies from this registry have no registry, older versions stored `This`
+    "UPDATE dependencies SET registry = NULL WHERE registry = 'This'",
+    "ALTER TABLE dependencies ADD COLUMN artifact TEXT;
+    ALTER TABLE dependencies ADD COLUMN bindep_target TEXT;
+    ALTER TABLE dependencies ADD COLUMN lib INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE dependencies ADD COLUMN public INTEGER;",
+    // Older versions stored the authorization token of whoever yanked a version
+    "UPDATE versions SET yanked_by = NULL",
+];
+
+/// Tables holding one row per list entry of a version, see `add_details`
+const DETAIL_TABLES: &[&str] = &["authors", "keywords", "categories", "features", "dependencies", "badges"];
+
 fn connect() -> Result<Connection, rusqlite::Error> {
     Connection::open(&CONFIG.database.path)
 }
 
+/// Writes that are only stored together by `commit`, dropping them without commit rolls them back.
+/// The database stays locked for other writers until then.
+pub(crate) struct PendingChanges {
+    connection: Connection,
+    committed: bool,
+}
+
+/// Starts collecting changes to the configured database
+pub(crate) fn begin() -> Result<PendingChanges, rusqlite::Error> {
+    begin_at(&CONFIG.database.path)
+}
+
+pub(crate) fn begin_at(database_path: &Path) -> Result<PendingChanges, rusqlite::Error> {
+    let connection = Connection::open(database_path)?;
+    connection.execute_batch("BEGIN IMMEDIATE")?;
+    Ok(PendingChanges { connection, committed: false })
+}
+
+impl PendingChanges {
+    pub(crate) fn add_package(&self, package: &PublishedPackage, cksum: &str) -> Result<(), rusqlite::Error> {
+        add_package(&self.connection, package, cksum)
+    }
+
+    pub(crate) fn complete_package(&self, package: &PublishedPackage, cksum: &str) -> Result<(), rusqlite::Error> {
+        complete_package(&self.connection, package, cksum)
+    }
+
+    pub(crate) fn set_yanked(&self, crate_name: &str, version: &str, yanked: bool, metadata: Option<&YankMetadata>) -> Result<(), rusqlite::Error> {
+        set_yanked(&self.connection, crate_name, version, yanked, metadata)
+    }
+
+    pub(crate) fn commit(mut self) -> Result<(), rusqlite::Error> {
+        self.connection.execute_batch("COMMIT")?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for PendingChanges {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = self.connection.execute_batch("ROLLBACK");
+        }
+    }
+}
+
 pub(crate) fn add_owner(crate_name: &str, owner: &str) -> Result<(), AddOwnerError> {
     let mut con = connect()?;
     let con = Transaction::new(&mut con, TransactionBehavior::Deferred)?;
@@ -90,50 +200,268 @@ pub(crate) fn get_all_packages() -> Result<Vec<(String, String, usize)>, rusqlit
     it.collect()
 }
 
-pub(crate) fn add_package(package: &PublishedPackage) -> Result<(), rusqlite::Error> {
-    let mut con = connect()?;
-    let con = Transaction::new(&mut con, TransactionBehavior::Deferred)?;
-    if !crate_is_in_db(&package.name)? {
+/// All published versions as (crate name, version)
+pub(crate) fn get_all_versions() -> Result<Vec<(String, String)>, rusqlite::Error> {
+    let con = connect()?;
+    let mut query = con.prepare(
+        "SELECT name, version FROM versions
+        INNER JOIN crates ON crates.crateId = versions.crateId")?;
+    let it = query.query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    it.collect()
+}
+
+/// Versions published before the database kept checksums and the other metadata, as (crate name, version)
+pub(crate) fn get_versions_without_checksum() -> Result<Vec<(String, String)>, rusqlite::Error> {
+    let con = connect()?;
+    let mut query = con.prepare(
+        "SELECT name, version FROM versions
+        INNER JOIN crates ON crates.crateId = versions.crateId
+        WHERE cksum IS NULL")?;
+    let it = query.query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    it.collect()
+}
+
+fn version_ids(con: &Connection, crate_name: &str, version: &str) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut query = con.prepare(
+        "SELECT versionId FROM versions
+        INNER JOIN crates ON crates.crateId = versions.crateId
+        WHERE name = ?1 AND version = ?2")?;
+    let it = query.query_map((crate_name, version), |row| row.get(0))?;
+    it.collect()
+}
+
+/// Adds the version inside `con`, it is only stored once the caller commits the transaction
+fn add_package(con: &Connection, package: &PublishedPackage, cksum: &str) -> Result<(), rusqlite::Error> {
+    let new_crates = con.execute(
+        "INSERT INTO crates (name) SELECT ?1
+        WHERE NOT EXISTS (SELECT crateId FROM crates WHERE name = ?1)", [&package.name])?;
+    if new_crates > 0 {
         println!("A new crate has been added!");
-        con.execute("INSERT INTO crates (name) VALUES (?1)", [&package.name])?;
     }
     let number_of_rows = con.execute(
         "INSERT INTO versions (version, description, documentation, homepage,
-        readme, readme_file, license, license_file, repository, crateId)
+        readme, readme_file, license, license_file, repository, rust_version, links, cksum, crateId)
         SELECT * FROM (
-            (VALUES ((?1), (?2), (?3), (?4), (?5), (?6), (?7), (?8), (?9)))
-            CROSS JOIN (SELECT crateId FROM crates WHERE crates.name = (?10))
+            (VALUES ((?1), (?2), (?3), (?4), (?5), (?6), (?7), (?8), (?9), (?10), (?11), (?12)))
+            CROSS JOIN (SELECT crateId FROM crates WHERE crates.name = (?13))
         )", (   
             &package.vers, &package.description, &package.documentation, 
             &package.homepage, &package.readme, &package.readme_file, 
             &package.license, &package.license_file, &package.repository,
-            &package.name))?;
-    assert_eq!(number_of_rows, 1);
-    con.commit()
+            &package.rust_version, &package.links, cksum, &package.name))?;
+    if number_of_rows != 1 {
+        return Err(rusqlite::Error::StatementChangedRows(number_of_rows));
+    }
+    add_details(con, con.last_insert_rowid(), package)
+}
+
+/// Stores links, checksum and the metadata kept in `DETAIL_TABLES` for a version
+/// that was added before the database kept them, replacing whatever is there
+fn complete_package(con: &Connection, package: &PublishedPackage, cksum: &str) -> Result<(), rusqlite::Error> {
+    for version_id in version_ids(con, &package.name, &package.vers)? {
+        con.execute("UPDATE versions SET links = ?2, cksum = ?3 WHERE versionId = ?1", (version_id, &package.links, cksum))?;
+        remove_details(con, version_id)?;
+        add_details(con, version_id, package)?;
+    }
+    Ok(())
+}
+
+fn add_details(con: &Connection, version_id: i64, package: &PublishedPackage) -> Result<(), rusqlite::Error> {
+    for author in &package.authors {
+        con.execute("INSERT INTO authors (versionId, name) VALUES (?1, ?2)", (version_id, author))?;
+    }
+    for keyword in &package.keywords {
+        con.execute("INSERT INTO keywords (versionId, keyword) VALUES (?1, ?2)", (version_id, keyword))?;
+    }
+    for category in &package.categories {
+        con.execute("INSERT INTO categories (versionId, category) VALUES (?1, ?2)", (version_id, category))?;
+    }
+    for (name, enables) in &package.features {
+        con.execute("INSERT INTO features (versionId, name, enables) VALUES (?1, ?2, ?3)", (version_id, name, to_json(enables)?))?;
+    }
+    for dep in &package.deps {
+        con.execute(
+            "INSERT INTO dependencies (versionId, name, version_req, features, optional, default_features,
+            target, kind, registry, explicit_name_in_toml, artifact, bindep_target, lib, public)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)", (
+                version_id, &dep.name, &dep.version_req, to_json(&dep.features)?, dep.optional, dep.default_features,
+                &dep.target, to_json_string(&dep.kind)?, dep.registry.as_ref().map(to_json_string).transpose()?,
+                &dep.explicit_name_in_toml, dep.artifact.as_ref().map(to_json).transpose()?, &dep.bindep_target,
+                dep.lib, dep.public))?;
+    }
+    for (badge, attributes) in &package.badges {
+        if attributes.is_empty() {
+            con.execute("INSERT INTO badges (versionId, badge) VALUES (?1, ?2)", (version_id, badge))?;
+        }
+        for (attribute, value) in attributes {
+            con.execute("INSERT INTO badges (versionId, badge, attribute, value) VALUES (?1, ?2, ?3, ?4)",
+                (version_id, badge, attribute, value))?;
+        }
+    }
+    Ok(())
 }
 
-pub(crate) fn crate_is_in_db(package: &str) -> Result<bool, rusqlite::Error> {
+fn remove_details(con: &Connection, version_id: i64) -> Result<(), rusqlite::Error> {
+    for table in DETAIL_TABLES {
+        con.execute(&format!("DELETE FROM {table} WHERE versionId = ?1"), [version_id])?;
+    }
+    Ok(())
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, rusqlite::Error> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+/// Like `to_json`, but values serialized as JSON strings are stored without quotes
+fn to_json_string<T: Serialize>(value: &T) -> Result<String, rusqlite::Error> {
+    match serde_json::to_value(value) {
+        Ok(Value::String(s)) => Ok(s),
+        Ok(other) => Ok(other.to_string()),
+        Err(e) => Err(rusqlite::Error::ToSqlConversionFailure(Box::new(e))),
+    }
+}
+
+fn from_json<T: DeserializeOwned>(row: &Row, index: usize) -> Result<T, rusqlite::Error> {
+    let json: String = row.get(index)?;
+    serde_json::from_str(&json).map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+}
+
+/// Sets the yanked state of a version. Reason, actor and time are replaced by `metadata`,
+/// so unyanking clears them.
+fn set_yanked(con: &Connection, crate_name: &str, version: &str, yanked: bool, metadata: Option<&YankMetadata>) -> Result<(), rusqlite::Error> {
+    con.execute(
+        "UPDATE versions SET yanked = ?3, yank_reason = ?4, yanked_by = ?5, yanked_at = ?6
+        WHERE version = ?2
+        AND crateId IN (
+            SELECT crateId FROM crates WHERE crates.name = ?1
+        )", (
+            crate_name, version, yanked,
+            metadata.and_then(|m| m.reason.as_ref()),
+            metadata.and_then(|m| m.actor.as_ref()),
+            metadata.map(|m| &m.time)))?;
+    Ok(())
+}
+
+/// Versions of the crate `crate_name` or of all crates, ordered by crate and publication
+pub(crate) fn get_version_results(crate_name: Option<&str>) -> Result<Vec<VersionResult>, rusqlite::Error> {
+    version_results(&connect()?, crate_name)
+}
+
+fn version_results(con: &Connection, crate_name: Option<&str>) -> Result<Vec<VersionResult>, rusqlite::Error> {
+    let mut query = con.prepare(
+        "SELECT name, version, description, license, rust_version,
+            yanked, yank_reason, yanked_by, yanked_at, links, cksum,
+            (SELECT json_group_array(name) FROM
+                (SELECT name FROM authors WHERE authors.versionId = versions.versionId ORDER BY rowid)),
+            (SELECT json_group_array(keyword) FROM
+                (SELECT keyword FROM keywords WHERE keywords.versionId = versions.versionId ORDER BY rowid)),
+            (SELECT json_group_array(category) FROM
+                (SELECT category FROM categories WHERE categories.versionId = versions.versionId ORDER BY rowid)),
+            (SELECT json_group_object(name, json(enables)) FROM features WHERE features.versionId = versions.versionId)
+        FROM versions
+        INNER JOIN crates ON crates.crateId = versions.crateId
+        WHERE ?1 IS NULL OR name = ?1
+        ORDER BY name, versionId")?;
+    let it = query.query_map([crate_name], |row| {
+        let license: Option<String> = row.get(3)?;
+        Ok(VersionResult {
+            crate_name: row.get(0)?,
+            num: row.get(1)?,
+            description: row.get(2)?,
+            licenses: license.as_deref().map(license::licenses).unwrap_or_default(),
+            license,
+            rust_version: row.get(4)?,
+            yanked: row.get(5)?,
+            yank_reason: row.get(6)?,
+            yanked_by: row.get(7)?,
+            yanked_at: row.get(8)?,
+            links: row.get(9)?,
+            checksum: row.get(10)?,
+            authors: from_json(row, 11)?,
+            keywords: from_json(row, 12)?,
+            categories: from_json(row, 13)?,
+            features: from_json(row, 14)?,
+        })
+    })?;
+    it.collect()
+}
+
+/// Marks dependencies whose registry URL `is_own` recognizes as dependencies from this registry.
+/// Returns the number of changed rows.
+pub(crate) fn clear_own_registry_urls<F: Fn(&str) -> bool>(is_own: F) -> Result<usize, rusqlite::Error> {
+    let mut con = connect()?;
+    let transaction = Transaction::new(&mut con, TransactionBehavior::Immediate)?;
+    let registries = transaction.prepare("SELECT DISTINCT registry FROM dependencies WHERE registry IS NOT NULL")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut changed = 0;
+    for registry in registries.iter().filter(|r| is_own(r)) {
+        changed += transaction.execute("UPDATE dependencies SET registry = NULL WHERE registry = ?1", [registry])?;
+    }
+    transaction.commit()?;
+    Ok(changed)
+}
+
+/// Unyanked versions depending on the crate `crate_name` from this registry.
+/// Names are compared after dash/underscore and case normalization.
+pub(crate) fn get_reverse_dependencies(crate_name: &str) -> Result<Vec<ReverseDependency>, rusqlite::Error> {
+    reverse_dependencies(&connect()?, crate_name)
+}
+
+fn reverse_dependencies(con: &Connection, crate_name: &str) -> Result<Vec<ReverseDependency>, rusqlite::Error> {
+    let mut query = con.prepare(
+        "SELECT crates.name, version, version_req, optional, default_features, features, target, kind
+        FROM dependencies
+        INNER JOIN versions ON versions.versionId = dependencies.versionId
+        INNER JOIN crates ON crates.crateId = versions.crateId
+        WHERE replace(lower(dependencies.name), '-', '_') = replace(lower(?1), '-', '_')
+        AND registry IS NULL AND NOT yanked
+        ORDER BY crates.name, versions.versionId")?;
+    let it = query.query_map([crate_name], |row| {
+        Ok(ReverseDependency {
+            crate_name: row.get(0)?,
+            num: row.get(1)?,
+            req: row.get(2)?,
+            optional: row.get(3)?,
+            default_features: row.get(4)?,
+            features: from_json(row, 5)?,
+            target: row.get(6)?,
+            kind: row.get(7)?,
+        })
+    })?;
+    it.collect()
+}
+
+/// Keywords of all versions keyed by (crate name, version)
+pub(crate) fn get_all_keywords() -> Result<HashMap<(String, String), Vec<String>>, rusqlite::Error> {
     let con = connect()?;
-    let mut check_for_package = con.prepare("SELECT crateId FROM crates WHERE name=?1")?;
-    let exists = check_for_package.query_map([&package], |row| {
-        row.get::<usize, usize>(0)
-    })?.count() > 0;
-    Ok(exists)
+    let mut query = con.prepare(
+        "SELECT name, version, keyword FROM keywords
+        INNER JOIN versions ON versions.versionId = keywords.versionId
+        INNER JOIN crates ON crates.crateId = versions.crateId")?;
+    let mut keywords: HashMap<_, Vec<_>> = HashMap::new();
+    let mut rows = query.query([])?;
+    while let Some(row) = rows.next()? {
+        keywords.entry((row.get(0)?, row.get(1)?)).or_default().push(row.get(2)?);
+    }
+    Ok(keywords)
 }
 
-pub(crate) fn get_description(crate_name: &str, version: &str) -> Result<Option<String>, rusqlite::Error> {
+/// Descriptions of all versions keyed by (crate name, version)
+pub(crate) fn get_all_descriptions() -> Result<HashMap<(String, String), String>, rusqlite::Error> {
     let con = connect()?;
-    let mut check_for_package = con.prepare(
-        "SELECT description FROM versions
+    let mut query = con.prepare(
+        "SELECT name, version, description FROM versions
         INNER JOIN crates ON crates.crateId = versions.crateId
-        WHERE name=?1 AND version=?2")?;
-    let result = check_for_package.query_map([crate_name, version], |row| {
-        row.get::<usize, Option<String>>(0)
-    })?.next();
-    match result {
-        Some(x) => x,
-        None => Ok(None)
-    }
+        WHERE description IS NOT NULL")?;
+    let it = query.query_map([], |row| {
+        Ok(((row.get(0)?, row.get(1)?), row.get(2)?))
+    })?;
+    it.collect()
 }
 
 pub(crate) fn init(database_path: &Path) -> Result<(), rusqlite::Error> {
@@ -173,5 +501,111 @@ pub(crate) fn init(database_path: &Path) -> Result<(), rusqlite::Error> {
             FOREIGN KEY(user) REFERENCES users(userId),
             FOREIGN KEY(crate) REFERENCES crates(crateId)
         )", ())?;
+    migrate(database_path)
+}
+
+/// Applies all migrations the database at `database_path` is missing
+pub(crate) fn migrate(database_path: &Path) -> Result<(), rusqlite::Error> {
+    let mut connection = Connection::open(database_path)?;
+    let applied: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let transaction = Transaction::new(&mut connection, TransactionBehavior::Exclusive)?;
+    for (number, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        println!("Migrating database to schema version {}", number + 1);
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", number + 1)?;
+    }
+    transaction.commit()
+}
+
+/// Writes a consistent copy of the database at `database_path` to the new file `target`
+pub(crate) fn backup(database_path: &Path, target: &Path) -> Result<(), rusqlite::Error> {
+    let connection = Connection::open(database_path)?;
+    connection.execute("VACUUM INTO ?1", [target.to_string_lossy()])?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{Connection, Transaction, TransactionBehavior};
+    use super::{init, migrate, begin_at, MIGRATIONS, add_package, complete_package, version_results, reverse_dependencies};
+    use crate::publish::{PublishedPackage, tests::package_json};
+
+    #[test]
+    fn new_database_has_all_migrations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database");
+        init(&path).unwrap();
+        let version: usize = Connection::open(&path).unwrap()
+            .query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        // Applying again changes nothing
+        migrate(&path).unwrap();
+    }
+
+    #[test]
+    fn pending_changes_are_only_stored_on_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database");
+        init(&path).unwrap();
+        let foo: PublishedPackage = serde_json::from_str(&package_json("foo", "")).unwrap();
+        let changes = begin_at(&path).unwrap();
+        changes.add_package(&foo, "abc").unwrap();
+        drop(changes);
+        let con = Connection::open(&path).unwrap();
+        assert!(version_results(&con, Some("foo")).unwrap().is_empty());
+
+        let changes = begin_at(&path).unwrap();
+        changes.add_package(&foo, "abc").unwrap();
+        changes.set_yanked("foo", "0.1.0", true, None).unwrap();
+        changes.commit().unwrap();
+        let versions = version_results(&con, Some("foo")).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert!(versions[0].yanked);
+    }
+
+    #[test]
+    fn stored_yank_tokens_are_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database");
+        init(&path).unwrap();
+        let con = Connection::open(&path).unwrap();
+        con.execute_batch("INSERT INTO crates (name) VALUES ('foo');
+            INSERT INTO versions (version, crateId, yanked, yanked_by, yanked_at) VALUES ('0.1.0', 1, 1, 'secret-token', 'now');").unwrap();
+        con.pragma_update(None, "user_version", MIGRATIONS.len() - 1).unwrap();
+        migrate(&path).unwrap();
+        let versions = version_results(&con, Some("foo")).unwrap();
+        assert_eq!(versions[0].yanked_by, None);
+        assert_eq!(versions[0].yanked_at.as_deref(), Some("now"));
+    }
+
+    #[test]
+    fn package_details_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database");
+        init(&path).unwrap();
+        let mut con = Connection::open(&path).unwrap();
+
+        let dependency = r#"{"name":"foo","version_req":"^0.1","features":["std"],"optional":true,"default_features":false,
+            "target":null,"kind":"dev","registry":null,"explicit_name_in_toml":null}"#;
+        let bar: PublishedPackage = serde_json::from_str(&package_json("bar", "")
+            .replace(r#""deps":[]"#, &format!("\"deps\":[{dependency}]"))
+            .replace(r#""authors":[]"#, r#""authors":["B","A"]"#)
+            .replace(r#""features":{}"#, r#""features":{"default":["std"],"std":[]}"#)
+            .replace(r#""keywords":[]"#, r#""keywords":["parser"]"#)).unwrap();
+        let transaction = Transaction::new(&mut con, TransactionBehavior::Deferred).unwrap();
+        add_package(&transaction, &bar, "abc").unwrap();
+        // Completing again replaces instead of duplicating
+        complete_package(&transaction, &bar, "def").unwrap();
+        transaction.commit().unwrap();
+
+        let versions = version_results(&con, Some("bar")).unwrap();
+        assert_eq!(versions[0].checksum.as_deref(), Some("def"));
+        assert_eq!(versions[0].authors, ["B", "A"]);
+        assert_eq!(versions[0].keywords, ["parser"]);
+        assert_eq!(versions[0].features["default"], ["std"]);
+        let dependents = reverse_dependencies(&con, "Foo").unwrap();
+        assert_eq!(dependents.len(), 1);
+        assert_eq!((dependents[0].crate_name.as_str(), dependents[0].kind.as_str()), ("bar", "dev"));
+        assert_eq!(dependents[0].features, ["std"]);
+    }
+}
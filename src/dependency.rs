@@ -1,10 +1,15 @@
-use serde::{Deserialize, Serialize};
+use semver::VersionReq;
+use serde::{Deserialize, Serialize, de::Error};
 use std::fmt::{Display, Formatter, Result as FMTResult};
+use url::Url;
 
 use crate::config::CONFIG;
 
+pub(crate) mod error;
+pub(crate) mod policy;
 
 #[derive(Deserialize, Debug, Clone)]
+#[serde(remote = "Self")]
 pub(crate) struct Dependency {
     pub(crate) name: String,
     pub(crate) version_req: String,
@@ -13,8 +18,49 @@ pub(crate) struct Dependency {
     pub(crate) default_features: bool,
     pub(crate) target: Option<String>,
     pub(crate) kind: DependencyKind,
+    /// `None` for dependencies from this registry
+    #[serde(default, deserialize_with = "deserialize_registry")]
     pub(crate) registry: Option<ValidRegistry>,
     pub(crate) explicit_name_in_toml: Option<String>,
+    /// Artifact kinds of an artifact dependency, like `bin`, `bin:name`, `cdylib` or `staticlib`
+    #[serde(default)]
+    pub(crate) artifact: Option<Vec<String>>,
+    /// The target an artifact dependency is built for
+    #[serde(default)]
+    pub(crate) bindep_target: Option<String>,
+    /// Whether the library of an artifact dependency can be used as well
+    #[serde(default)]
+    pub(crate) lib: bool,
+    /// Whether the dependency is part of the public API
+    #[serde(default)]
+    pub(crate) public: Option<bool>,
+}
+
+impl<'de> Deserialize<'de> for Dependency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de> {
+        let this = Self::deserialize(deserializer)?;
+        if let Err(e) = VersionReq::parse(&this.version_req) {
+            return Err(D::Error::custom(format!("invalid version requirement \"{}\" for dependency {}: {e}", this.version_req, this.name)));
+        }
+        match &this.artifact {
+            None if this.lib || this.bindep_target.is_some() => {
+                return Err(D::Error::custom(format!("dependency {} sets lib or target without being an artifact dependency", this.name)));
+            },
+            Some(kinds) if kinds.is_empty() || !kinds.iter().all(|kind| is_valid_artifact_kind(kind)) => {
+                return Err(D::Error::custom(format!("invalid artifact kinds {kinds:?} for dependency {}", this.name)));
+            },
+            _ => {},
+        }
+        Ok(this)
+    }
+}
+
+/// The artifact kinds cargo knows, where `bin:name` selects a single binary
+fn is_valid_artifact_kind(kind: &str) -> bool {
+    matches!(kind, "bin" | "cdylib" | "staticlib")
+        || kind.strip_prefix("bin:").is_some_and(|name| !name.is_empty())
 }
 
 impl Default for Dependency {
@@ -28,7 +74,11 @@ impl Default for Dependency {
             target: None, 
             kind: DependencyKind::Normal, 
             registry: None, 
-            explicit_name_in_toml: None 
+            explicit_name_in_toml: None,
+            artifact: None,
+            bindep_target: None,
+            lib: false,
+            public: None,
         }
     }
 }
@@ -41,26 +91,150 @@ pub(crate) enum DependencyKind {
     Normal,
 }
 
+const CRATES_IO_GIT: &str = "https://github.com/rust-lang/crates.io-index";
+const CRATES_IO_SPARSE: &str = "sparse+https://index.crates.io/";
+
+/// Reads the `registry` of a dependency, where this registry itself becomes `None` like cargo expects.
+/// This registry is named by the `This` older versions wrote, its public index URL or a previous one.
+pub(crate) fn deserialize_registry<'de, D>(deserializer: D) -> Result<Option<ValidRegistry>, D::Error>
+    where D: serde::Deserializer<'de> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(url) if !is_this_registry(&url) => ValidRegistry::try_from(url).map(Some).map_err(D::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn is_this_registry(url: &str) -> bool {
+    let own_urls = CONFIG.index.public_url.iter().chain(&CONFIG.index.previous_urls);
+    url == "This" || names_registry(url, own_urls)
+}
+
+fn names_registry<'a>(url: &str, registry_urls: impl IntoIterator<Item = &'a String>) -> bool {
+    let Some(canonical) = canonical_url(url) else {
+        return false;
+    };
+    registry_urls.into_iter().any(|own| canonical_url(own).as_ref() == Some(&canonical))
+}
+
+/// The registry of a dependency from another registry, given by the URL of its index in git or sparse form
 #[derive(Deserialize, Debug, Serialize, Clone)]
-#[non_exhaustive]
+#[serde(try_from = "String", into = "String")]
 pub(crate) enum ValidRegistry {
-    This,
-    #[serde(rename = "https://github.com/rust-lang/crates.io-index")]
+    /// Written as the git URL like crates.io does, but also recognized by its sparse URL
     CratesIO,
+    /// The URL as given, compared by its canonical form
+    Other(String),
+}
+
+impl ValidRegistry {
+    /// Makes different spellings of the same index URL equal, the way cargo identifies registries
+    fn canonical(&self) -> String {
+        match self {
+            Self::CratesIO => CRATES_IO_GIT.to_string(),
+            Self::Other(url) => canonical_url(url).unwrap_or_else(|| url.clone()),
+        }
+    }
+}
+
+/// `None` if `url` is neither a git URL nor a `sparse+http(s)` URL
+fn canonical_url(url: &str) -> Option<String> {
+    if let Some(sparse) = url.strip_prefix("sparse+") {
+        let parsed = Url::parse(sparse).ok().filter(|u| matches!(u.scheme(), "http" | "https"))?;
+        let url = parsed.as_str();
+        return Some(format!("sparse+{url}{}", if url.ends_with('/') { "" } else { "/" }));
+    }
+    let parsed = Url::parse(url).ok().filter(|u| matches!(u.scheme(), "http" | "https" | "ssh" | "git" | "file"))?;
+    let url = parsed.as_str().trim_end_matches('/');
+    Some(url.strip_suffix(".git").unwrap_or(url).to_string())
+}
+
+impl TryFrom<String> for ValidRegistry {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let canonical = canonical_url(&value).ok_or_else(|| format!("invalid registry index URL \"{value}\""))?;
+        if canonical == CRATES_IO_GIT || canonical == CRATES_IO_SPARSE {
+            return Ok(Self::CratesIO);
+        }
+        Ok(Self::Other(value))
+    }
+}
+
+impl From<ValidRegistry> for String {
+    fn from(value: ValidRegistry) -> Self {
+        value.to_string()
+    }
+}
+
+impl PartialEq for ValidRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical() == other.canonical()
+    }
 }
 
 impl Display for ValidRegistry {
     fn fmt(&self, f: &mut Formatter<'_>) -> FMTResult {
         match self {
-            Self::CratesIO => write!(f, "https://github.com/rust-lang/crates.io-index"),
-            Self::This => write!(f, "{}", &CONFIG.index.path.display())
+            Self::CratesIO => write!(f, "{CRATES_IO_GIT}"),
+            Self::Other(url) => write!(f, "{url}"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::DependencyKind;
+    use super::{Dependency, DependencyKind, ValidRegistry, names_registry};
+
+    fn dependency_json(version_req: &str) -> String {
+        format!(r#"{{"name":"serde","version_req":"{version_req}","features":[],"optional":false,
+            "default_features":true,"target":null,"kind":"normal","registry":null,"explicit_name_in_toml":null}}"#)
+    }
+
+    #[test]
+    fn deserialize_version_req() {
+        assert!(serde_json::from_str::<Dependency>(&dependency_json("^1.0.0-beta.1")).is_ok());
+        assert!(serde_json::from_str::<Dependency>(&dependency_json(">=1.2, <2")).is_ok());
+        assert!(serde_json::from_str::<Dependency>(&dependency_json("latest")).is_err());
+    }
+
+    #[test]
+    fn registries_in_git_and_sparse_form() {
+        let registry = |url: &str| serde_json::from_str::<ValidRegistry>(&format!("\"{url}\""));
+        assert_eq!(registry("https://github.com/rust-lang/crates.io-index").unwrap(), ValidRegistry::CratesIO);
+        assert_eq!(registry("sparse+https://index.crates.io/").unwrap(), ValidRegistry::CratesIO);
+        assert_eq!(serde_json::to_string(&registry("sparse+https://index.crates.io").unwrap()).unwrap(),
+            "\"https://github.com/rust-lang/crates.io-index\"");
+        let internal = registry("sparse+https://crates.example.com/index").unwrap();
+        assert_eq!(internal, registry("sparse+https://CRATES.example.com/index/").unwrap());
+        assert_ne!(internal, registry("https://crates.example.com/index").unwrap());
+        assert_eq!(serde_json::to_string(&internal).unwrap(), "\"sparse+https://crates.example.com/index\"");
+        assert_eq!(registry("ssh://git@git.example.com/index.git").unwrap(), registry("ssh://git@git.example.com/index").unwrap());
+        assert!(registry("crates-io").is_err());
+        assert!(registry("sparse+ftp://example.com/").is_err());
+    }
+
+    #[test]
+    fn deserialize_artifact_fields() {
+        let with = |extra: &str| serde_json::from_str::<Dependency>(&dependency_json("^1").replace('}', &format!(",{extra}}}")));
+        let dependency = with(r#""artifact":["bin:tool","cdylib"],"bindep_target":"wasm32-unknown-unknown","lib":true,"public":true"#).unwrap();
+        assert_eq!(dependency.artifact.as_deref(), Some(&["bin:tool".to_string(), "cdylib".to_string()][..]));
+        assert_eq!(dependency.bindep_target.as_deref(), Some("wasm32-unknown-unknown"));
+        assert!(dependency.lib);
+        assert_eq!(dependency.public, Some(true));
+        assert!(with(r#""lib":true"#).is_err());
+        assert!(with(r#""artifact":["dylib"]"#).is_err());
+        assert!(with(r#""artifact":[]"#).is_err());
+    }
+
+    #[test]
+    fn same_registry_dependencies_have_no_registry() {
+        let dependency: Dependency = serde_json::from_str(&dependency_json("^1").replace("\"registry\":null", "\"registry\":\"This\"")).unwrap();
+        assert!(dependency.registry.is_none());
+        let own = ["sparse+https://crates.example.com/index/".to_string(), "https://git.example.com/index.git".to_string()];
+        assert!(names_registry("sparse+https://crates.example.com/index", &own));
+        assert!(names_registry("https://git.example.com/index", &own));
+        assert!(!names_registry("https://github.com/rust-lang/crates.io-index", &own));
+    }
 
     #[test]
     fn deserialize_dependencykind_normal() {
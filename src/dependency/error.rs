@@ -0,0 +1,23 @@
+use std::{
+    error::Error,
+    fmt::{Formatter, Display, Result as FMTResult},
+};
+
+/// A dependency of a published crate that the dependency policy does not allow
+#[derive(Debug, PartialEq)]
+pub(crate) enum DependencyPolicyError {
+    /// A dependency from this registry that is not in the index, like a git or path dependency
+    MissingCrate(String),
+    NoMatchingVersion { name: String, req: String },
+    DisallowedRegistry { name: String, registry: String },
+}
+impl Error for DependencyPolicyError {}
+impl Display for DependencyPolicyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FMTResult {
+        match self {
+            Self::MissingCrate(name) => write!(f, "dependency {name} does not exist in this registry"),
+            Self::NoMatchingVersion { name, req } => write!(f, "no version of dependency {name} in this registry matches {req}"),
+            Self::DisallowedRegistry { name, registry } => write!(f, "dependency {name} comes from the registry {registry}, which is not allowed"),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use semver::{Version, VersionReq};
+
+use crate::{config::DependencyPolicyConfig, index::IndexCrate};
+
+use super::{Dependency, error::DependencyPolicyError};
+
+/// Every dependency of `deps` that is neither in this registry with a matching unyanked version
+/// nor from one of the allowed registries. `versions` looks up the versions of a crate in this registry.
+pub(crate) fn violations<F>(deps: &[Dependency], config: &DependencyPolicyConfig, versions: F) -> Vec<DependencyPolicyError>
+    where F: Fn(&str) -> Option<Vec<IndexCrate>> {
+    deps.iter()
+        .filter_map(|dep| match &dep.registry {
+            None => check_local(dep, &versions),
+            Some(registry) => (!config.allowed_registries.contains(registry))
+                .then(|| DependencyPolicyError::DisallowedRegistry { name: dep.name.clone(), registry: registry.to_string() }),
+        })
+        .collect()
+}
+
+fn check_local<F>(dep: &Dependency, versions: F) -> Option<DependencyPolicyError>
+    where F: Fn(&str) -> Option<Vec<IndexCrate>> {
+    let Some(versions) = versions(&dep.name) else {
+        return Some(DependencyPolicyError::MissingCrate(dep.name.clone()));
+    };
+    // The requirement was validated when the publish request was read
+    let req = VersionReq::parse(&dep.version_req).ok()?;
+    let matches = versions.iter()
+        .filter(|c| !c.yanked)
+        .any(|c| Version::parse(&c.vers).is_ok_and(|v| req.matches(&v)));
+    (!matches).then(|| DependencyPolicyError::NoMatchingVersion { name: dep.name.clone(), req: dep.version_req.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::violations;
+    use crate::{
+        config::DependencyPolicyConfig,
+        dependency::{Dependency, ValidRegistry, error::DependencyPolicyError},
+        index::IndexCrate,
+    };
+
+    fn dependency(name: &str, version_req: &str, registry: Option<ValidRegistry>) -> Dependency {
+        Dependency { name: name.to_string(), version_req: version_req.to_string(), registry, ..Default::default() }
+    }
+
+    fn versions(name: &str) -> Option<Vec<IndexCrate>> {
+        let version = |vers: &str, yanked| IndexCrate { name: "utils".to_string(), vers: vers.to_string(), yanked, ..Default::default() };
+        (name == "utils").then(|| vec![version("1.2.0", false), version("2.0.0", true)])
+    }
+
+    #[test]
+    fn local_dependencies_need_matching_version() {
+        let config = DependencyPolicyConfig::default();
+        assert!(violations(&[dependency("utils", "^1.1", None)], &config, versions).is_empty());
+        assert_eq!(violations(&[dependency("utils", "^2", None), dependency("helpers", "^1", None)], &config, versions), [
+            DependencyPolicyError::NoMatchingVersion { name: "utils".to_string(), req: "^2".to_string() },
+            DependencyPolicyError::MissingCrate("helpers".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn registries_must_be_allowed() {
+        let deps = [dependency("serde", "^1", Some(ValidRegistry::CratesIO))];
+        assert!(violations(&deps, &DependencyPolicyConfig::default(), versions).is_empty());
+        let config = DependencyPolicyConfig { allowed_registries: vec![], ..Default::default() };
+        assert!(matches!(&violations(&deps, &config, versions)[..], [DependencyPolicyError::DisallowedRegistry { .. }]));
+
+        let internal = ValidRegistry::Other("sparse+https://crates.example.com/".to_string());
+        let config = DependencyPolicyConfig { allowed_registries: vec![internal], ..Default::default() };
+        let deps = [dependency("billing", "^1", Some(ValidRegistry::Other("sparse+https://crates.example.com".to_string())))];
+        assert!(violations(&deps, &config, versions).is_empty());
+    }
+}
@@ -7,6 +7,7 @@ use std::{
 
 use crate::{
     error::ReturnJson,
+    config::CONFIG,
     http::{Response, Byteable}
 };
 
@@ -18,4 +19,8 @@ pub fn handle(mut stream: TcpStream, path: &str) -> IoResult<()> {
         Err(e) => Response::new(500).body(ReturnJson::from(vec![e])).into_bytes(),
     };
     stream.write_all(&response)
-}
\ No newline at end of file
+}
+/// Location of the stored .crate file of a published version
+pub(crate) fn crate_file_path(crate_name: &str, version: &str) -> PathBuf {
+    PathBuf::from(format!("{}/{}/{version}/download", CONFIG.download.path, crate_name.to_lowercase()))
+}
@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::dependency::{Dependency, DependencyKind};
+
+use self::error::FeatureError;
+
+pub(crate) mod error;
+
+/// Checks every feature value of `features` against the other features and `deps` the way cargo does
+/// and looks for features enabling each other in a loop. Returns all problems found.
+pub(crate) fn validate(features: &HashMap<String, Vec<String>>, deps: &[Dependency]) -> Vec<FeatureError> {
+    // Sorted, so problems are always reported in the same order
+    let features: BTreeMap<&str, &Vec<String>> = features.iter().map(|(name, values)| (name.as_str(), values)).collect();
+    // Dev-dependencies cannot be enabled by features, so they are left out
+    let deps: HashMap<&str, bool> = deps.iter()
+        .filter(|d| d.kind != DependencyKind::Dev)
+        .map(|d| (d.explicit_name_in_toml.as_deref().unwrap_or(&d.name), d.optional))
+        .fold(HashMap::new(), |mut deps, (name, optional)| {
+            // A dependency can appear once per target and kind, it is optional if any of them is
+            *deps.entry(name).or_default() |= optional;
+            deps
+        });
+    // Optional dependencies only get an implicit feature if no feature refers to them with `dep:`
+    let explicit: HashSet<&str> = features.values()
+        .flat_map(|values| values.iter())
+        .filter_map(|value| value.strip_prefix("dep:"))
+        .collect();
+    let implicit = |name: &str| deps.get(name) == Some(&true) && !explicit.contains(name);
+
+    let mut errors = vec![];
+    for (&feature, &values) in &features {
+        if !is_valid_name(feature) {
+            errors.push(FeatureError::InvalidName(feature.to_string()));
+        }
+        if implicit(feature) {
+            errors.push(FeatureError::NameConflict(feature.to_string()));
+        }
+        for value in values {
+            let (dependency, needs_optional) = if let Some(dependency) = value.strip_prefix("dep:") {
+                (dependency, true)
+            } else if let Some((dependency, _)) = value.split_once('/') {
+                match dependency.strip_suffix('?') {
+                    Some(dependency) => (dependency, true),
+                    None => (dependency, false),
+                }
+            } else {
+                if !features.contains_key(value.as_str()) && !implicit(value) {
+                    errors.push(FeatureError::UnknownFeature { feature: feature.to_string(), value: value.clone() });
+                }
+                continue;
+            };
+            match deps.get(dependency) {
+                None => errors.push(FeatureError::UnknownDependency { feature: feature.to_string(), dependency: dependency.to_string() }),
+                Some(false) if needs_optional => errors.push(FeatureError::NotOptional { feature: feature.to_string(), dependency: dependency.to_string() }),
+                Some(_) => {},
+            }
+        }
+    }
+    errors.extend(cycles(&features).into_iter().map(FeatureError::Cycle));
+    errors
+}
+
+/// Cargo's rules: a letter, digit or `_` first, then letters, digits, `_`, `-`, `+` or `.`
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.'))
+}
+
+/// Loops of features enabling features, each reported once as the path from its first feature back to it
+fn cycles(features: &BTreeMap<&str, &Vec<String>>) -> Vec<Vec<String>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State { Unvisited, OnPath, Done }
+
+    fn visit<'a>(
+        feature: &'a str,
+        features: &BTreeMap<&'a str, &'a Vec<String>>,
+        states: &mut HashMap<&'a str, State>,
+        path: &mut Vec<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        states.insert(feature, State::OnPath);
+        path.push(feature);
+        let enabled = features.get(feature).into_iter().flat_map(|values| values.iter())
+            .map(String::as_str)
+            .filter(|value| features.contains_key(value));
+        for next in enabled {
+            match states.get(next).copied().unwrap_or(State::Unvisited) {
+                State::Unvisited => visit(next, features, states, path, cycles),
+                State::OnPath => {
+                    let start = path.iter().position(|f| *f == next).expect("features on the path are in it");
+                    cycles.push(path[start..].iter().chain([&next]).map(ToString::to_string).collect());
+                },
+                State::Done => {},
+            }
+        }
+        path.pop();
+        states.insert(feature, State::Done);
+    }
+
+    let mut states = HashMap::new();
+    let mut cycles = vec![];
+    for feature in features.keys() {
+        if !states.contains_key(feature) {
+            visit(feature, features, &mut states, &mut vec![], &mut cycles);
+        }
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::{validate, error::FeatureError};
+    use crate::dependency::{Dependency, DependencyKind};
+
+    fn features(features: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        features.iter()
+            .map(|(name, values)| (name.to_string(), values.iter().map(ToString::to_string).collect()))
+            .collect()
+    }
+
+    fn deps() -> Vec<Dependency> {
+        let dependency = |name: &str, optional, kind| Dependency { name: name.to_string(), optional, kind, ..Default::default() };
+        vec![
+            dependency("serde", true, DependencyKind::Normal),
+            dependency("log", false, DependencyKind::Normal),
+            dependency("cc", true, DependencyKind::Build),
+            dependency("criterion", false, DependencyKind::Dev),
+            Dependency { explicit_name_in_toml: Some("json".to_string()), ..dependency("serde_json", true, DependencyKind::Normal) },
+        ]
+    }
+
+    #[test]
+    fn valid_features() {
+        let features = features(&[
+            ("default", &["std", "serde"]),
+            ("std", &["log/std", "serde?/std"]),
+            ("json", &["dep:json", "serde/derive"]),
+            ("native", &["cc"]),
+        ]);
+        assert_eq!(validate(&features, &deps()), []);
+    }
+
+    #[test]
+    fn dangling_references() {
+        let features = features(&[
+            ("a", &["missing", "log", "dep:log", "criterion/html", "json/std", "nothing?/x"]),
+            ("serde", &[]),
+            ("bad/name", &[]),
+        ]);
+        assert_eq!(validate(&features, &deps()), [
+            FeatureError::UnknownFeature { feature: "a".to_string(), value: "missing".to_string() },
+            FeatureError::UnknownFeature { feature: "a".to_string(), value: "log".to_string() },
+            FeatureError::NotOptional { feature: "a".to_string(), dependency: "log".to_string() },
+            FeatureError::UnknownDependency { feature: "a".to_string(), dependency: "criterion".to_string() },
+            FeatureError::UnknownDependency { feature: "a".to_string(), dependency: "nothing".to_string() },
+            FeatureError::InvalidName("bad/name".to_string()),
+            FeatureError::NameConflict("serde".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn explicit_dependency_hides_implicit_feature() {
+        let features = features(&[("a", &["dep:serde"]), ("b", &["serde"])]);
+        assert_eq!(validate(&features, &deps()), [
+            FeatureError::UnknownFeature { feature: "b".to_string(), value: "serde".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let features = features(&[("a", &["b"]), ("b", &["c"]), ("c", &["a", "d"]), ("d", &[]), ("e", &["e"])]);
+        assert_eq!(validate(&features, &deps()), [
+            FeatureError::Cycle(vec!["a".to_string(), "b".to_string(), "c".to_string(), "a".to_string()]),
+            FeatureError::Cycle(vec!["e".to_string(), "e".to_string()]),
+        ]);
+    }
+}
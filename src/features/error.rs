@@ -0,0 +1,38 @@
+use std::{
+    error::Error,
+    fmt::{Formatter, Display, Result as FMTResult},
+};
+
+/// A problem in the `[features]` table of a published crate
+#[derive(Debug, PartialEq)]
+pub(crate) enum FeatureError {
+    InvalidName(String),
+    /// A feature and an optional dependency without `dep:` references share a name
+    NameConflict(String),
+    /// `value` is neither a feature nor the implicit feature of an optional dependency
+    UnknownFeature { feature: String, value: String },
+    /// `dep:x`, `x/y` or `x?/y` where `x` is no normal or build dependency
+    UnknownDependency { feature: String, dependency: String },
+    /// `dep:x` or `x?/y` where `x` is not optional
+    NotOptional { feature: String, dependency: String },
+    /// Features enabling each other in a loop, the first one repeated at the end
+    Cycle(Vec<String>),
+}
+impl Error for FeatureError {}
+impl Display for FeatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FMTResult {
+        match self {
+            Self::InvalidName(name) => write!(f, "invalid feature name \"{name}\", feature names start with a letter, digit or _ \
+                and only contain letters, digits, _, -, + or ."),
+            Self::NameConflict(name) => write!(f, "feature \"{name}\" has the same name as an optional dependency, \
+                refer to the dependency with \"dep:{name}\" to use both"),
+            Self::UnknownFeature { feature, value } => write!(f, "feature \"{feature}\" includes \"{value}\", \
+                which is neither a feature nor an optional dependency"),
+            Self::UnknownDependency { feature, dependency } => write!(f, "feature \"{feature}\" refers to \"{dependency}\", \
+                which is not a dependency"),
+            Self::NotOptional { feature, dependency } => write!(f, "feature \"{feature}\" refers to \"{dependency}\" \
+                as optional, but it is not an optional dependency"),
+            Self::Cycle(path) => write!(f, "features enable each other in a cycle: {}", path.join(" -> ")),
+        }
+    }
+}
@@ -1,32 +1,182 @@
 use std::{
-    path::Path, 
-    process::{Command, Stdio}, 
-    io::Result as IoResult,
-    env::{current_dir, set_current_dir},
+    path::Path,
+    process::{Command, Stdio},
+    io::{Error as IoError, ErrorKind, Result as IoResult},
 };
-use crate::config::CONFIG;
+use time::{OffsetDateTime, UtcOffset, format_description::well_known::Rfc3339};
+use crate::{config::CONFIG, mirror};
 
+/// Commits the file at `relative_path` inside the index and notifies the mirrors.
+/// If committing fails the file is unstaged again, so callers can restore it.
 pub(crate) fn add_and_commit_to_index<P: AsRef<Path>>(relative_path: &P, message: &str) -> IoResult<()> {
-    let current_dir = current_dir()?;
-    set_current_dir(&CONFIG.index.path)?;
-    Command::new("git")
-        .arg("add")
-        .arg(relative_path.as_ref())
-        .stdout(Stdio::null())
-        .status()?;
-    Command::new("git")
-        .args(["commit", "-m", message, "--no-gpg-sign"])
-        .stdout(Stdio::null())
-        .status()?;
-    set_current_dir(current_dir)
+    add_and_commit(&CONFIG.index.path, relative_path.as_ref(), message)?;
+    mirror::notify();
+    Ok(())
 }
 
 pub(crate) fn init_index() -> IoResult<()> {
-    let current_dir = current_dir()?;
-    set_current_dir(&CONFIG.index.path)?;
-    Command::new("git")
-        .args(["init"])
-        .stdout(Stdio::null())
-        .status()?;
-    set_current_dir(current_dir)
-}
\ No newline at end of file
+    run_git(&CONFIG.index.path, &["init", "--quiet"]).map(drop)
+}
+
+/// Pushes the currently checked out branch of the repository at `repository` to `remote`,
+/// which can be a configured remote name, an URL or a path to a (bare) repository.
+pub(crate) fn push(repository: &Path, remote: &str) -> IoResult<()> {
+    run_git(repository, &["push", "--quiet", remote, "HEAD"]).map(drop)
+}
+
+pub(crate) fn head_commit(repository: &Path) -> IoResult<String> {
+    run_git(repository, &["rev-parse", "HEAD"])
+}
+
+/// Number of commits reachable from HEAD but not from `since`, or all commits if there is no `since`
+pub(crate) fn commits_since(repository: &Path, since: Option<&str>) -> IoResult<usize> {
+    let range = since.map_or_else(|| "HEAD".to_string(), |s| format!("{s}..HEAD"));
+    run_git(repository, &["rev-list", "--count", &range])?
+        .parse()
+        .map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+}
+
+/// The newest commit reachable from HEAD that was committed at or before `time`
+pub(crate) fn last_commit_before(repository: &Path, time: OffsetDateTime) -> IoResult<Option<String>> {
+    let time = time.to_offset(UtcOffset::UTC).format(&Rfc3339).map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+    let commit = run_git(repository, &["rev-list", "-1", &format!("--before={time}"), "HEAD"])?;
+    Ok(Some(commit).filter(|c| !c.is_empty()))
+}
+
+/// Content of the file at `path` in `commit`, or `None` if the commit or the file does not exist
+pub(crate) fn file_at_commit(repository: &Path, commit: &str, path: &Path) -> IoResult<Option<Vec<u8>>> {
+    let object = format!("{commit}:{}", path.to_string_lossy());
+    let exists = Command::new("git")
+        .current_dir(repository)
+        .args(["cat-file", "-e", &object])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?
+        .success();
+    if !exists || run_git(repository, &["cat-file", "-t", &object])? != "blob" {
+        return Ok(None);
+    }
+    run_git_raw(repository, &["cat-file", "blob", &object]).map(Some)
+}
+
+fn add_and_commit(repository: &Path, relative_path: &Path, message: &str) -> IoResult<()> {
+    let path = relative_path.to_string_lossy();
+    run_git(repository, &["add", "--", &path])?;
+    if let Err(e) = run_git(repository, &["commit", "--quiet", "--no-gpg-sign", "-m", message]) {
+        // Fails without any commit yet, in which case there is nothing else to keep staged anyway
+        let _ = run_git(repository, &["reset", "--quiet", "--", &path]);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Runs git inside `repository` and returns its trimmed standard output
+/// or an error containing standard error if git did not exit successfully
+fn run_git(repository: &Path, args: &[&str]) -> IoResult<String> {
+    run_git_raw(repository, args).map(|output| String::from_utf8_lossy(&output).trim().to_string())
+}
+
+fn run_git_raw(repository: &Path, args: &[&str]) -> IoResult<Vec<u8>> {
+    let output = Command::new("git")
+        .current_dir(repository)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(IoError::other(format!("git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, process::Command, fs::write};
+    use time::OffsetDateTime;
+    use super::{push, head_commit, commits_since, last_commit_before, file_at_commit, add_and_commit, run_git};
+
+    fn git(dir: &Path, args: &[&str]) {
+        git_at(dir, args, "");
+    }
+
+    /// Runs git with `GIT_COMMITTER_DATE` set, an empty date means now
+    fn git_at(dir: &Path, args: &[&str], date: &str) {
+        let status = Command::new("git")
+            .current_dir(dir)
+            .env("GIT_COMMITTER_DATE", date)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@localhost", "-c", "commit.gpgsign=false"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn push_to_local_bare_repository() {
+        let index = tempfile::tempdir().unwrap();
+        let remote = tempfile::tempdir().unwrap();
+        git(index.path(), &["init", "--quiet"]);
+        git(remote.path(), &["init", "--quiet", "--bare"]);
+        write(index.path().join("config.json"), "{}").unwrap();
+        git(index.path(), &["add", "config.json"]);
+        git(index.path(), &["commit", "--quiet", "-m", "Init index"]);
+
+        let remote_path = remote.path().to_str().unwrap();
+        assert_eq!(commits_since(index.path(), None).unwrap(), 1);
+        push(index.path(), remote_path).unwrap();
+        let head = head_commit(index.path()).unwrap();
+        assert_eq!(head_commit(remote.path()).unwrap(), head);
+        assert_eq!(commits_since(index.path(), Some(&head)).unwrap(), 0);
+    }
+
+    #[test]
+    fn push_to_missing_remote_fails() {
+        let index = tempfile::tempdir().unwrap();
+        git(index.path(), &["init", "--quiet"]);
+        write(index.path().join("config.json"), "{}").unwrap();
+        git(index.path(), &["add", "config.json"]);
+        git(index.path(), &["commit", "--quiet", "-m", "Init index"]);
+        let missing = index.path().join("does-not-exist");
+        assert!(push(index.path(), missing.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn file_at_past_commit() {
+        let index = tempfile::tempdir().unwrap();
+        git(index.path(), &["init", "--quiet"]);
+        write(index.path().join("foo"), "first\n").unwrap();
+        git(index.path(), &["add", "foo"]);
+        git_at(index.path(), &["commit", "--quiet", "-m", "First"], "@1000000000 +0000");
+        let first = head_commit(index.path()).unwrap();
+        write(index.path().join("foo"), "second\n").unwrap();
+        git(index.path(), &["commit", "--quiet", "-a", "-m", "Second"]);
+
+        assert_eq!(last_commit_before(index.path(), OffsetDateTime::UNIX_EPOCH).unwrap(), None);
+        let commit = last_commit_before(index.path(), OffsetDateTime::from_unix_timestamp(1_500_000_000).unwrap()).unwrap().unwrap();
+        assert_eq!(commit, first);
+        assert_eq!(file_at_commit(index.path(), &commit, Path::new("foo")).unwrap().unwrap(), b"first\n");
+        assert_eq!(file_at_commit(index.path(), &commit, Path::new("bar")).unwrap(), None);
+        assert_eq!(file_at_commit(index.path(), "0000000", Path::new("foo")).unwrap(), None);
+    }
+
+    #[test]
+    fn failed_commit_is_unstaged() {
+        let index = tempfile::tempdir().unwrap();
+        git(index.path(), &["init", "--quiet"]);
+        git(index.path(), &["config", "user.name", "Test"]);
+        git(index.path(), &["config", "user.email", "test@localhost"]);
+        write(index.path().join("config.json"), "{}").unwrap();
+        add_and_commit(index.path(), Path::new("config.json"), "Init index").unwrap();
+
+        let hook = index.path().join(".git/hooks/pre-commit");
+        write(&hook, "#!/bin/sh\nexit 1\n").unwrap();
+        std::fs::set_permissions(&hook, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        write(index.path().join("foo"), "{}\n").unwrap();
+        assert!(add_and_commit(index.path(), Path::new("foo"), "Add foo").is_err());
+        assert_eq!(run_git(index.path(), &["diff", "--cached", "--name-only"]).unwrap(), "");
+        assert_eq!(commits_since(index.path(), None).unwrap(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    error::Error,
+    io::{Write, Result as IoResult},
+    net::TcpStream,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::Serialize;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+mod error;
+
+use crate::{
+    config::CONFIG,
+    error::ReturnJson,
+    git,
+    http::{Response, Byteable},
+    index::IndexCrate,
+};
+
+use self::error::HistoryError;
+
+/// Prints the index file of `crate_name` as of the last index commit at or before `timestamp`
+pub(crate) fn run(crate_name: &str, timestamp: &str) -> Result<(), Box<dyn Error>> {
+    let time = parse_timestamp(timestamp)?;
+    let (commit, entries) = entries_at(crate_name, time)?;
+    eprintln!("Index of {crate_name} at commit {commit}");
+    for entry in entries {
+        println!("{}", entry.index_line()?);
+    }
+    Ok(())
+}
+
+/// `GET /api/v1/crates/{name}/index?at={timestamp}`
+pub(crate) fn handle_entries_request(mut stream: TcpStream, crate_name: &str, query: &str) -> IoResult<()> {
+    println!("INDEX HISTORY {crate_name} {query}");
+    let result = query.split('&')
+        .find_map(|pair| pair.strip_prefix("at="))
+        .ok_or_else(|| HistoryError::InvalidTimestamp(String::new()))
+        .and_then(|timestamp| parse_timestamp(&percent_decode(timestamp)))
+        .and_then(|time| entries_at(crate_name, time));
+    let response = match result {
+        Ok((commit, entries)) => Response::new(200).body(serde_json::to_string(&EntriesJson { commit, entries })?),
+        Err(HistoryError::IoError(e)) => return Err(e),
+        Err(e) => {
+            let code = match e {
+                HistoryError::InvalidTimestamp(_) | HistoryError::InvalidCommit => 400,
+                HistoryError::NoCommitBefore | HistoryError::CrateNotFound => 404,
+                HistoryError::IoError(_) | HistoryError::BadIndexJson(_, _) => 500,
+            };
+            Response::new(code).body(ReturnJson::new(&[e]))
+        }
+    };
+    stream.write_all(&response.into_bytes())
+}
+
+/// `GET /api/v1/index/at/{commit}/{path}`, serving the index as it was at `commit`
+/// so it can be used as `sparse+http://{host}/api/v1/index/at/{commit}/`
+pub(crate) fn handle_snapshot_request(mut stream: TcpStream, commit: &str, path: &[&str]) -> IoResult<()> {
+    println!("INDEX SNAPSHOT {commit} {}", path.join("/"));
+    let Some(path) = snapshot_path(commit, path) else {
+        return stream.write_all(&Response::new(400).body(ReturnJson::new(&[HistoryError::InvalidCommit])).into_bytes());
+    };
+    let response = match git::file_at_commit(&CONFIG.index.path, commit, &path)? {
+        Some(content) => Response::new(200).body(content),
+        None => Response::new(404).body(ReturnJson::new(&["file does not exist at that commit"])),
+    };
+    stream.write_all(&response.into_bytes())
+}
+
+#[derive(Serialize, Debug)]
+struct EntriesJson {
+    commit: String,
+    entries: Vec<IndexCrate>,
+}
+
+fn entries_at(crate_name: &str, time: OffsetDateTime) -> Result<(String, Vec<IndexCrate>), HistoryError> {
+    if crate_name.is_empty() {
+        return Err(HistoryError::CrateNotFound);
+    }
+    let index = &CONFIG.index.path;
+    let commit = git::last_commit_before(index, time)?.ok_or(HistoryError::NoCommitBefore)?;
+    let path = IndexCrate { name: crate_name.to_string(), ..Default::default() }.path_in_index();
+    let content = git::file_at_commit(index, &commit, &path)?.ok_or(HistoryError::CrateNotFound)?;
+    let entries = parse_index_file(&String::from_utf8_lossy(&content))?;
+    Ok((commit, entries))
+}
+
+fn parse_index_file(content: &str) -> Result<Vec<IndexCrate>, HistoryError> {
+    content.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| HistoryError::BadIndexJson(e, i)))
+        .collect()
+}
+
+/// RFC 3339 like `2023-05-01T12:00:00Z` or seconds since the epoch
+fn parse_timestamp(timestamp: &str) -> Result<OffsetDateTime, HistoryError> {
+    OffsetDateTime::parse(timestamp, &Rfc3339).ok()
+        .or_else(|| timestamp.parse().ok().and_then(|s| OffsetDateTime::from_unix_timestamp(s).ok()))
+        .ok_or_else(|| HistoryError::InvalidTimestamp(timestamp.to_string()))
+}
+
+/// Accepts only hexadecimal commit ids and plain relative paths, so nothing can be passed to git as an option
+fn snapshot_path(commit: &str, path: &[&str]) -> Option<PathBuf> {
+    if !(4..=40).contains(&commit.len()) || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let path: PathBuf = path.iter().collect();
+    let plain = path.components().all(|c| matches!(c, Component::Normal(_)));
+    (plain && path != Path::new("")).then_some(path)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::{parse_timestamp, percent_decode, snapshot_path, parse_index_file};
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("2023-05-01T12:00:00Z").unwrap().unix_timestamp(), 1_682_942_400);
+        assert_eq!(parse_timestamp(&percent_decode("2023-05-01T14%3A00%3A00%2B02%3A00")).unwrap().unix_timestamp(), 1_682_942_400);
+        assert_eq!(parse_timestamp("1682942400").unwrap().unix_timestamp(), 1_682_942_400);
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+
+    #[test]
+    fn snapshot_paths() {
+        assert_eq!(snapshot_path("abc123", &["3", "f", "foo"]), Some(PathBuf::from("3/f/foo")));
+        assert_eq!(snapshot_path("abc123", &["config.json"]), Some(PathBuf::from("config.json")));
+        assert_eq!(snapshot_path("HEAD", &["config.json"]), None);
+        assert_eq!(snapshot_path("--output", &["config.json"]), None);
+        assert_eq!(snapshot_path("abc123", &["..", "secret"]), None);
+        assert_eq!(snapshot_path("abc123", &[]), None);
+    }
+
+    #[test]
+    fn index_file_with_bad_line() {
+        let content = "{\"name\":\"foo\",\"vers\":\"0.1.0\",\"deps\":[],\"cksum\":\"\",\"features\":{},\"yanked\":false,\"v\":2}\nnot json\n";
+        assert!(parse_index_file(content).is_err());
+        assert_eq!(parse_index_file(content.lines().next().unwrap()).unwrap().len(), 1);
+    }
+}
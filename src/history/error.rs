@@ -0,0 +1,45 @@
+use std::{
+    error::Error,
+    fmt::{Formatter, Display, Result as FMTResult},
+    io::Error as IoError,
+};
+
+use serde_json::error::Error as SerdeJsonError;
+
+#[derive(Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub(crate) enum HistoryError {
+    IoError(IoError),
+    InvalidTimestamp(String),
+    InvalidCommit,
+    NoCommitBefore,
+    CrateNotFound,
+    BadIndexJson(SerdeJsonError, usize),
+}
+impl Error for HistoryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IoError(i) => Some(i),
+            Self::BadIndexJson(j, _) => Some(j),
+            Self::InvalidTimestamp(_) | Self::InvalidCommit | Self::NoCommitBefore | Self::CrateNotFound => None,
+        }
+    }
+}
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FMTResult {
+        write!(f, "failed to read index history: {}", match self {
+            Self::IoError(e) => e.to_string(),
+            Self::InvalidTimestamp(t) => format!("invalid timestamp {t}, expected RFC 3339 or seconds since the epoch"),
+            Self::InvalidCommit => "invalid commit id".to_string(),
+            Self::NoCommitBefore => "the index has no commit before that time".to_string(),
+            Self::CrateNotFound => "crate is not in the index at that time".to_string(),
+            Self::BadIndexJson(e, line) => format!("bad index json at line {line}: {e}"),
+        })
+    }
+}
+
+impl From<IoError> for HistoryError {
+    fn from(value: IoError) -> Self {
+        Self::IoError(value)
+    }
+}
@@ -77,6 +77,10 @@ impl Response<NeedsHeaders> {
             body: vec![], 
         }
     }
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.front_matter = format!("{}\r\n{name}: {value}", self.front_matter);
+        self
+    }
     pub fn body<B: Into<Vec<u8>>>(self, body: B) -> Response<NeedsMessage> {
         Response { body: body.into(), marker: PhantomData, front_matter: self.front_matter }
     }
@@ -100,6 +104,7 @@ fn standard_marker_from_code(code: u32) -> &'static str {
 
         400 => "BAD REQUEST",
         403 => "FORBIDDEN",
+        404 => "NOT FOUND",
         405 => "METHOD NOT ALLOWED",
         411 => "LENGTH REQUIRED",
         413 => "PAYLOAD TOO LARGE",
@@ -130,4 +135,9 @@ mod tests {
         let result = Response::new(200).body("SomeBODY");
         assert_eq!(result.into_bytes(), b"HTTP/1.1 200 OK\r\n\r\nSomeBODY");
     }
+    #[test]
+    fn response_headers_before_body() {
+        let result = Response::new(200).header("Content-Type", "text/html").body("SomeBODY");
+        assert_eq!(result.into_bytes(), b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\nSomeBODY");
+    }
 }
\ No newline at end of file
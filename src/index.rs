@@ -1,38 +1,55 @@
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     path::{PathBuf, Path}, fs::OpenOptions, io::Write,
+    sync::Mutex,
 };
 
 use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 use walkdir::WalkDir;
 use crate::{
     publish::PublishedPackage, 
-    dependency::{DependencyKind, Dependency, ValidRegistry},
+    dependency::{DependencyKind, Dependency, ValidRegistry, deserialize_registry},
     config::{CONFIG, IndexConfigFile}
 };
 
 use self::error::WalkIndexError;
 
 pub(crate) mod error;
+pub(crate) mod cache;
+pub(crate) mod migrate;
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+/// Held while index files are written and committed, so concurrent requests do not interleave
+pub(crate) static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub(crate) struct IndexCrate {
     pub(crate) name: String,
     pub(crate) vers: String,
     pub(crate) deps: Vec<IndexDependency>,
     pub(crate) cksum: String,
-    pub(crate) features: HashMap<String, Vec<String>>,
+    pub(crate) features: BTreeMap<String, Vec<String>>,
     pub(crate) yanked: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) links: Option<String>,
     pub(crate) v: VValue,
-    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
-    pub(crate) features2: HashMap<String, Vec<String>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub(crate) features2: BTreeMap<String, Vec<String>>,
+    /// Minimum supported Rust version, used by cargo's MSRV-aware resolver
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) rust_version: Option<String>,
+    /// RFC 3339 timestamp of the publication
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) pubtime: Option<String>,
+    /// Fields this registry does not know, kept so rewriting a line loses nothing
+    #[serde(flatten)]
+    pub(crate) extra: Map<String, Value>,
 }
 
 impl IndexCrate {
     pub(crate) fn new(value: PublishedPackage, file: &[u8]) -> Self {
-        type Features = HashMap<String, Vec<String>>;
+        type Features = BTreeMap<String, Vec<String>>;
         let (new_features, old_features): (Features, Features) = value.features.into_iter()
             .partition(|(_, x)| x.iter().any(|w| w.contains('?')|| w.contains(':')));
         IndexCrate {
@@ -45,8 +62,17 @@ impl IndexCrate {
             links: value.links, 
             v: if new_features.is_empty() {VValue::V1} else {VValue::V2}, 
             features2: new_features,
+            rust_version: value.rust_version,
+            pubtime: Some(pubtime_now()),
+            extra: Map::new(),
         }
     }
+    /// The JSON line of this entry in its index file, without line ending.
+    /// Features are kept in sorted maps, so equal entries always serialize to equal bytes.
+    pub(crate) fn index_line(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
     pub fn path_in_index(&self) -> PathBuf {
         let charcount = self.name.chars().count();
         assert!(charcount > 0);
@@ -63,7 +89,13 @@ impl IndexCrate {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Current UTC time in the format crates.io uses for `pubtime`, e.g. `2023-05-01T12:00:00Z`
+pub(crate) fn pubtime_now() -> String {
+    let now = OffsetDateTime::now_utc().replace_nanosecond(0).expect("0 is a valid nanosecond");
+    now.format(&Rfc3339).expect("current time is always formattable")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct IndexDependency {
     name: String,
     req: String,
@@ -72,10 +104,21 @@ pub(crate) struct IndexDependency {
     default_features: bool,
     target: Option<String>,
     kind: DependencyKind,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_registry")]
     registry: Option<ValidRegistry>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    package: Option<String>
+    package: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    artifact: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bindep_target: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    lib: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public: Option<bool>,
+    /// Fields this registry does not know, kept so rewriting a line loses nothing
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 
@@ -91,11 +134,36 @@ impl From<Dependency> for IndexDependency {
             kind: value.kind,
             registry: value.registry,
             package: value.explicit_name_in_toml.is_some().then_some(value.name),
+            artifact: value.artifact,
+            bindep_target: value.bindep_target,
+            lib: value.lib,
+            public: value.public,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl From<IndexDependency> for Dependency {
+    fn from(value: IndexDependency) -> Self {
+        Dependency {
+            name: value.package.clone().unwrap_or(value.name.clone()),
+            version_req: value.req,
+            features: value.features,
+            optional: value.optional,
+            default_features: value.default_features,
+            target: value.target,
+            kind: value.kind,
+            registry: value.registry,
+            explicit_name_in_toml: value.package.is_some().then_some(value.name),
+            artifact: value.artifact,
+            bindep_target: value.bindep_target,
+            lib: value.lib,
+            public: value.public,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Default, Clone)]
 pub(crate) enum VValue {
     #[default]
     V1 = 1,
@@ -122,57 +190,98 @@ impl Serialize for VValue {
             S: serde::Serializer {
         match self {
             VValue::V1 => serializer.serialize_i8(1),
-            VValue::V2 => serializer.serialize_i8(2)
+            VValue::V2 => serializer.serialize_i8(2),
         }
     }
 }
 
-pub(crate) fn walk_index_crates_with_file_predicate<P: Fn(&Path) -> bool>(predicate: P) -> impl Iterator<Item = Result<IndexCrate, WalkIndexError>> {
+/// Paths of all crate files in the index, leaving out git internals and `config.json`
+pub(crate) fn index_files() -> impl Iterator<Item = PathBuf> {
     WalkDir::new(&CONFIG.index.path).into_iter().flatten()
     .filter(|p| 
         p.path().is_file() 
         && !p.path().starts_with(CONFIG.index.path.join(".git")) 
         && p.path() != CONFIG.index.path.join("config.json"))
-    .filter(move |p| predicate(p.path()))
+    .map(walkdir::DirEntry::into_path)
+}
+
+pub(crate) fn walk_index_crates_with_file_predicate<P: Fn(&Path) -> bool>(predicate: P) -> impl Iterator<Item = Result<IndexCrate, WalkIndexError>> {
+    index_files()
+    .filter(move |p| predicate(p))
     .flat_map(|f| {
-        let s = match std::fs::read_to_string(f.path()) {
-            Err(i) => return vec![Err(WalkIndexError::IoError(i, f.into_path()))],
+        let s = match std::fs::read_to_string(&f) {
+            Err(i) => return vec![Err(WalkIndexError::IoError(i, f))],
             Ok(s) => s,
         };
         s.lines()
             .enumerate()
             .map(|(i, line)| {
                 serde_json::from_str(line)
-                .map_err(|e| WalkIndexError::ParseJson(e, f.clone().into_path(), i))
+                .map_err(|e| WalkIndexError::ParseJson(e, f.clone(), i))
             })
             .collect::<Vec<_>>()
     })
 }
 
+/// Content of an index file holding `crates`: one JSON line each, terminated by `\n` like crates.io
+pub(crate) fn index_file_content(crates: &[IndexCrate]) -> serde_json::Result<String> {
+    crates.iter()
+        .map(|c| c.index_line().map(|line| line + "\n"))
+        .collect()
+}
+
 pub(crate) fn walk_index_crates() -> impl Iterator<Item = Result<IndexCrate, WalkIndexError>> {
     walk_index_crates_with_file_predicate(|_| true)
 }
 
+/// Writes `content` to a temporary file next to `path` and renames it over `path`,
+/// so readers never see a partially written file
+pub(crate) fn write_file_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| std::io::Error::other("no file name"))?;
+    let mut temporary_name = std::ffi::OsString::from(".");
+    temporary_name.push(file_name);
+    temporary_name.push(".tmp");
+    let temporary_path = path.with_file_name(temporary_name);
+    let mut file = std::fs::File::create(&temporary_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(temporary_path, path)
+}
+
 pub (crate) fn write_config_json(index_path: &Path) -> std::io::Result<()> {
     let config_path = index_path.join("config.json");
     
     let mut index_config = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(config_path)?;
 
     let json_struct: IndexConfigFile = CONFIG.clone().try_into().expect("Bad URL in configuration");
-    index_config.write_all(
-        serde_json::to_string_pretty(&json_struct)?
-            .replace('\n', "\r\n").as_bytes()
-    )?;
+    index_config.write_all(serde_json::to_string_pretty(&json_struct)?.as_bytes())?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{IndexDependency, VValue};
-    use crate::dependency::Dependency;
+    use std::collections::HashMap;
+    use super::{IndexCrate, IndexDependency, VValue, pubtime_now, index_file_content};
+    use crate::{dependency::Dependency, publish::{PublishedPackage, tests::package_json}};
+
+    fn package_with_features(features: &[(&str, &[&str])]) -> PublishedPackage {
+        let mut package: PublishedPackage = serde_json::from_str(&package_json("foo", "")).unwrap();
+        package.features = features.iter()
+            .map(|(name, values)| ((*name).to_string(), values.iter().map(ToString::to_string).collect()))
+            .collect::<HashMap<_, _>>();
+        package
+    }
+
+    #[test]
+    fn unknown_fields_survive_rewrite() {
+        let line = r#"{"name":"a","vers":"0.1.0","deps":[{"name":"b","req":"^1","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal","future_dep":[1]}],"cksum":"abc","features":{},"yanked":false,"v":2,"future":{"x":true}}"#;
+        let index_crate: IndexCrate = serde_json::from_str(line).unwrap();
+        assert_eq!(index_crate.index_line().unwrap(), line);
+    }
 
     #[test]
     fn dep_to_index_dep_no_rename() {
@@ -186,12 +295,82 @@ mod tests {
         assert_eq!(transformed.name, String::from("some_crate"));
     }
 
+    #[test]
+    fn artifact_dependencies_round_trip() {
+        let artifact_dependency = Dependency {
+            name: "tool".to_string(),
+            artifact: Some(vec!["bin".to_string()]),
+            bindep_target: Some("x86_64-unknown-linux-gnu".to_string()),
+            lib: true,
+            public: Some(false),
+            ..Default::default()
+        };
+        for (features, v) in [(&[][..], VValue::V1), (&[("serde", &["dep:serde"][..])][..], VValue::V2)] {
+            let mut package = package_with_features(features);
+            package.deps.push(artifact_dependency.clone());
+            let index_crate = IndexCrate::new(package, b"file");
+            assert_eq!(index_crate.v, v);
+            let line = index_crate.index_line().unwrap();
+            assert!(line.contains(r#""artifact":["bin"],"bindep_target":"x86_64-unknown-linux-gnu","lib":true,"public":false"#));
+            let read: IndexCrate = serde_json::from_str(&line).unwrap();
+            assert_eq!(read.v, v);
+            let dependency = Dependency::from(read.deps[0].clone());
+            assert_eq!(dependency.artifact, artifact_dependency.artifact);
+            assert_eq!(dependency.bindep_target, artifact_dependency.bindep_target);
+            assert_eq!((dependency.lib, dependency.public), (true, Some(false)));
+        }
+    }
+
     #[test]
     fn deserialize_vvalue() {
         let j: VValue = serde_json::from_str("1").unwrap();
         assert_eq!(j, VValue::V1);
     }
 
+    #[test]
+    fn identical_metadata_identical_lines() {
+        let features: &[(&str, &[&str])] = &[
+            ("default", &["std"]), ("std", &[]), ("alloc", &[]), ("serde", &["dep:serde", "std"]),
+            ("derive", &["serde?/derive"]), ("a", &[]), ("z", &["a"]), ("m", &["a", "z"]),
+        ];
+        let lines: Vec<_> = (0..10)
+            .map(|_| {
+                let mut index_crate = IndexCrate::new(package_with_features(features), b"file");
+                index_crate.pubtime = Some("2023-05-01T12:00:00Z".to_string());
+                index_crate.index_line().unwrap()
+            })
+            .collect();
+        assert!(lines.windows(2).all(|w| w[0] == w[1]));
+        assert!(lines[0].find(r#""alloc""#) < lines[0].find(r#""default""#));
+    }
+
+    #[test]
+    fn index_file_uses_unix_line_endings() {
+        let crates = vec![
+            IndexCrate { name: "a".to_string(), vers: "0.1.0".to_string(), ..Default::default() },
+            IndexCrate { name: "a".to_string(), vers: "0.2.0".to_string(), ..Default::default() },
+        ];
+        let content = index_file_content(&crates).unwrap();
+        assert_eq!(content.matches('\n').count(), 2);
+        assert!(content.ends_with("}\n"));
+        assert!(!content.contains('\r'));
+    }
+
+    #[test]
+    fn pubtime_has_second_precision() {
+        let pubtime = pubtime_now();
+        assert_eq!(pubtime.len(), "2023-05-01T12:00:00Z".len());
+        assert!(pubtime.ends_with('Z'));
+    }
+
+    #[test]
+    fn rust_version_only_serialized_if_present() {
+        let without = IndexCrate { name: "a".to_string(), ..Default::default() };
+        assert!(!serde_json::to_string(&without).unwrap().contains("rust_version"));
+        let with = IndexCrate { rust_version: Some("1.65".to_string()), ..without };
+        assert!(serde_json::to_string(&with).unwrap().contains(r#""rust_version":"1.65""#));
+    }
+
     #[test]
     fn dep_to_index_dep_rename() {
         let d = Dependency {
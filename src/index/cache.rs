@@ -0,0 +1,112 @@
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use super::{IndexCrate, walk_index_crates};
+
+/// All index entries, read from disk once and kept up to date by publish and yank
+static CACHE: LazyLock<RwLock<IndexCache>> = LazyLock::new(|| {
+    let mut cache = IndexCache::default();
+    for entry in walk_index_crates() {
+        match entry {
+            Ok(index_crate) => cache.insert(index_crate),
+            Err(e) => println!("Skipping index entry: {e}"),
+        }
+    }
+    RwLock::new(cache)
+});
+
+/// Reads the index into memory if that did not happen yet and returns the number of crates
+pub(crate) fn load() -> usize {
+    read().crates.len()
+}
+
+/// Versions of every crate, grouped by crate
+pub(crate) fn all() -> Vec<Vec<IndexCrate>> {
+    read().crates.values().cloned().collect()
+}
+
+pub(crate) fn insert(index_crate: IndexCrate) {
+    write().insert(index_crate);
+}
+
+pub(crate) fn set_yanked(name: &str, vers: &str, yanked: bool) -> bool {
+    write().set_yanked(name, vers, yanked)
+}
+
+/// The cache for checks that need a consistent view of the index, blocking publish and yank while held
+pub(crate) fn read() -> RwLockReadGuard<'static, IndexCache> {
+    CACHE.read().expect("index cache writer panicked")
+}
+
+fn write() -> RwLockWriteGuard<'static, IndexCache> {
+    CACHE.write().expect("index cache writer panicked")
+}
+
+/// Crate versions keyed by normalized crate name
+#[derive(Default)]
+pub(crate) struct IndexCache {
+    crates: BTreeMap<String, Vec<IndexCrate>>,
+}
+
+impl IndexCache {
+    /// All versions of the crate whose name equals `name` after dash/underscore and case normalization
+    pub(crate) fn versions(&self, name: &str) -> Option<&[IndexCrate]> {
+        self.crates.get(&normalize(name)).map(Vec::as_slice)
+    }
+
+    /// The name of every crate as it was first published
+    pub(crate) fn names(&self) -> Vec<String> {
+        self.crates.values()
+            .filter_map(|versions| versions.first().map(|c| c.name.clone()))
+            .collect()
+    }
+
+    pub(crate) fn insert(&mut self, index_crate: IndexCrate) {
+        self.crates.entry(normalize(&index_crate.name)).or_default().push(index_crate);
+    }
+
+    fn set_yanked(&mut self, name: &str, vers: &str, yanked: bool) -> bool {
+        let Some(version) = self.crates.get_mut(&normalize(name))
+            .and_then(|versions| versions.iter_mut().find(|c| c.name == name && c.vers == vers)) else {
+            return false;
+        };
+        version.yanked = yanked;
+        true
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexCache;
+    use crate::index::IndexCrate;
+
+    fn index_crate(name: &str, vers: &str) -> IndexCrate {
+        IndexCrate { name: name.to_string(), vers: vers.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn versions_are_found_by_normalized_name() {
+        let mut cache = IndexCache::default();
+        cache.insert(index_crate("foo-bar", "0.1.0"));
+        cache.insert(index_crate("foo-bar", "0.2.0"));
+        let versions = cache.versions("foo_bar").unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].name, "foo-bar");
+        assert!(cache.versions("foobar").is_none());
+    }
+
+    #[test]
+    fn set_yanked_needs_exact_version() {
+        let mut cache = IndexCache::default();
+        cache.insert(index_crate("foo", "0.1.0"));
+        assert!(!cache.set_yanked("foo", "0.2.0", true));
+        assert!(cache.set_yanked("foo", "0.1.0", true));
+        assert!(cache.versions("foo").unwrap()[0].yanked);
+    }
+}
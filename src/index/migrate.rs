@@ -0,0 +1,52 @@
+use std::{error::Error, fs};
+
+use crate::{config::CONFIG, database, dependency::is_this_registry, git::add_and_commit_to_index};
+use super::{IndexCrate, index_files, index_file_content, write_file_atomically};
+
+/// Rewrites every index file in canonical form (sorted feature maps, `\n` line endings,
+/// no `registry` for dependencies from this registry) and commits the result.
+/// Files containing unparsable lines are left untouched, fields this registry does not know are kept.
+/// Dependencies in the database naming this registry by one of its URLs lose that URL as well.
+pub(crate) fn run() -> Result<(), Box<dyn Error>> {
+    let (mut rewritten, mut skipped) = (0, 0);
+    for path in index_files() {
+        let content = fs::read_to_string(&path)?;
+        let crates: Result<Vec<IndexCrate>, _> = content.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect();
+        let crates = match crates {
+            Ok(crates) => crates,
+            Err(e) => {
+                println!("Skipping {}: {e}", path.display());
+                skipped += 1;
+                continue;
+            }
+        };
+        let canonical = index_file_content(&crates)?;
+        if canonical != content {
+            write_file_atomically(&path, canonical.as_bytes())?;
+            rewritten += 1;
+        }
+    }
+
+    let config_path = CONFIG.index.path.join("config.json");
+    let config = fs::read_to_string(&config_path)?;
+    if config.contains('\r') {
+        write_file_atomically(&config_path, config.replace("\r\n", "\n").as_bytes())?;
+        rewritten += 1;
+    }
+
+    if rewritten > 0 {
+        add_and_commit_to_index(&".", "Rewrite index files in canonical form")?;
+    }
+    println!("Rewrote {rewritten} file(s), skipped {skipped} file(s) with unparsable lines");
+    if CONFIG.database.path.is_file() {
+        database::migrate(&CONFIG.database.path)?;
+        println!("Removed the registry URL of {} same-registry dependencies in the database", database::clear_own_registry_urls(is_this_registry)?);
+    }
+    if skipped > 0 {
+        return Err(format!("{skipped} index file(s) could not be migrated").into());
+    }
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use spdx::{Expression, LicenseItem, LicenseReq, ParseMode};
+
+use crate::config::{LicensePolicyConfig, LicensePolicyMode};
+
+use self::error::LicenseError;
+
+pub(crate) mod error;
+
+/// The license requirements of the SPDX expression `license`, like `["MIT", "Apache-2.0"]`,
+/// empty if it cannot be parsed
+pub(crate) fn licenses(license: &str) -> Vec<String> {
+    parse(license)
+        .map(|expression| expression.requirements().map(|r| display(&r.req)).collect())
+        .unwrap_or_default()
+}
+
+/// Checks that a published crate has a license and that its SPDX expression is valid and accepted by `policy`.
+/// Returns a warning instead of failing for licenses the policy only warns about.
+pub(crate) fn check(license: Option<&str>, license_file: Option<&str>, policy: &LicensePolicyConfig) -> Result<Option<String>, LicenseError> {
+    let Some(license) = license else {
+        // The content of a license file cannot be checked
+        return license_file.map(|_| None).ok_or(LicenseError::Missing);
+    };
+    let expression = parse(license).map_err(|e| LicenseError::InvalidExpression(license.to_string(), e.reason.to_string()))?;
+    let Err(failures) = expression.evaluate_with_failures(|req| is_accepted(req, policy)) else {
+        return Ok(None);
+    };
+    let error = LicenseError::NotAllowed(failures.iter().map(|f| display(&f.req)).collect());
+    match policy.mode {
+        LicensePolicyMode::Deny => Err(error),
+        LicensePolicyMode::Warn => Ok(Some(error.to_string())),
+    }
+}
+
+/// Lax like cargo and crates.io, so `MIT/Apache-2.0` is accepted as well
+fn parse(license: &str) -> Result<Expression, spdx::ParseError> {
+    Expression::parse_mode(license, ParseMode::LAX)
+}
+
+/// Like the `Display` of `spdx`, but keeps the `-only` it strips from GNU licenses
+fn display(req: &LicenseReq) -> String {
+    match (&req.license, &req.exception) {
+        (LicenseItem::Spdx { id, or_later: false }, None) if id.is_gnu() && id.is_deprecated() => format!("{}-only", id.name),
+        (LicenseItem::Spdx { id, or_later: false }, Some(exception)) if id.is_gnu() && id.is_deprecated() => {
+            format!("{}-only WITH {}", id.name, exception.name)
+        },
+        _ => req.to_string(),
+    }
+}
+
+fn is_accepted(req: &LicenseReq, policy: &LicensePolicyConfig) -> bool {
+    let license = display(&LicenseReq { license: req.license.clone(), exception: None });
+    !policy.denied.iter().any(|entry| matches(&license, entry))
+        && (policy.allowed.is_empty() || policy.allowed.iter().any(|entry| matches(&license, entry)))
+}
+
+/// An entry matches the license of the same id and all licenses it is a dash separated prefix of,
+/// so `AGPL-3.0` covers `AGPL-3.0-only` and `AGPL-3.0-or-later`
+fn matches(license: &str, entry: &str) -> bool {
+    let (license, entry) = (license.to_lowercase(), entry.to_lowercase());
+    license == entry || license.strip_prefix(&entry).is_some_and(|rest| rest.starts_with(['-', '+']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, licenses, error::LicenseError};
+    use crate::config::{LicensePolicyConfig, LicensePolicyMode};
+
+    fn policy(allowed: &[&str], denied: &[&str]) -> LicensePolicyConfig {
+        LicensePolicyConfig {
+            allowed: allowed.iter().map(ToString::to_string).collect(),
+            denied: denied.iter().map(ToString::to_string).collect(),
+            mode: LicensePolicyMode::Deny,
+        }
+    }
+
+    #[test]
+    fn parses_expressions() {
+        assert_eq!(licenses("MIT OR Apache-2.0"), ["MIT", "Apache-2.0"]);
+        assert_eq!(licenses("MIT/Apache-2.0"), ["MIT", "Apache-2.0"]);
+        assert_eq!(licenses("Apache-2.0 WITH LLVM-exception"), ["Apache-2.0 WITH LLVM-exception"]);
+        assert_eq!(licenses("GPL-2.0-only WITH Classpath-exception-2.0 OR LGPL-2.1-or-later"),
+            ["GPL-2.0-only WITH Classpath-exception-2.0", "LGPL-2.1-or-later"]);
+        assert!(licenses("Proprietary!").is_empty());
+    }
+
+    #[test]
+    fn license_is_required() {
+        let policy = policy(&[], &[]);
+        assert_eq!(check(None, None, &policy), Err(LicenseError::Missing));
+        assert_eq!(check(None, Some("LICENSE.txt"), &policy), Ok(None));
+        assert!(matches!(check(Some("MIT AND"), None, &policy), Err(LicenseError::InvalidExpression(..))));
+        assert_eq!(check(Some("MIT"), None, &policy), Ok(None));
+    }
+
+    #[test]
+    fn denied_licenses() {
+        let deny_agpl = policy(&[], &["AGPL-3.0"]);
+        assert_eq!(check(Some("AGPL-3.0-or-later"), None, &deny_agpl), Err(LicenseError::NotAllowed(vec!["AGPL-3.0-or-later".to_string()])));
+        assert_eq!(check(Some("MIT OR AGPL-3.0-only"), None, &deny_agpl), Ok(None));
+        assert!(check(Some("MIT AND AGPL-3.0-only"), None, &deny_agpl).is_err());
+        assert_eq!(check(Some("GPL-3.0-only"), None, &deny_agpl), Ok(None));
+        assert_eq!(check(Some("AGPL-3.0-only"), None, &deny_agpl), Err(LicenseError::NotAllowed(vec!["AGPL-3.0-only".to_string()])));
+        assert!(check(Some("AGPL-3.0-only"), None, &policy(&[], &["AGPL-3.0-only"])).is_err());
+    }
+
+    #[test]
+    fn allowed_licenses() {
+        let mut policy = policy(&["MIT", "Apache-2.0"], &[]);
+        assert_eq!(check(Some("MIT OR Apache-2.0"), None, &policy), Ok(None));
+        assert!(check(Some("BSD-3-Clause"), None, &policy).is_err());
+        policy.mode = LicensePolicyMode::Warn;
+        assert!(check(Some("BSD-3-Clause"), None, &policy).unwrap().is_some_and(|w| w.contains("BSD-3-Clause")));
+    }
+}
@@ -0,0 +1,24 @@
+use std::{
+    error::Error,
+    fmt::{Formatter, Display, Result as FMTResult},
+};
+
+#[derive(Debug, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub(crate) enum LicenseError {
+    Missing,
+    /// The expression and why it could not be parsed
+    InvalidExpression(String, String),
+    /// Licenses of the expression the policy does not accept, if it cannot be satisfied without them
+    NotAllowed(Vec<String>),
+}
+impl Error for LicenseError {}
+impl Display for LicenseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FMTResult {
+        match self {
+            Self::Missing => write!(f, "either license or license_file must be set"),
+            Self::InvalidExpression(expression, reason) => write!(f, "license \"{expression}\" is no valid SPDX expression: {reason}"),
+            Self::NotAllowed(licenses) => write!(f, "the license policy of this registry does not allow {}", licenses.join(", ")),
+        }
+    }
+}
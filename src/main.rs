@@ -10,6 +10,7 @@ use threads::ThreadPool;
 use http::{Request, Response, RequestMethod, Byteable};
 use config::CONFIG;
 use error::ReturnJson;
+use cli::{ARGUMENTS, Command};
 
 mod http;
 mod threads;
@@ -24,12 +25,38 @@ mod config;
 mod error;
 mod owners;
 mod database;
+mod mirror;
+mod cli;
+mod check;
+mod rebuild;
+mod crate_file;
+mod crate_info;
+mod web;
+mod snapshot;
+mod history;
+mod version;
+mod name_policy;
+mod features;
+mod license;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    match ARGUMENTS.command {
+        Command::Serve => serve(),
+        Command::Check { repair } => check::run(repair),
+        Command::Rebuild => rebuild::run(),
+        Command::MigrateIndex => index::migrate::run(),
+        Command::Export { ref path, with_git } => snapshot::export(path, with_git),
+        Command::Import { ref path } => snapshot::import(path),
+        Command::IndexAt { ref crate_name, ref timestamp } => history::run(crate_name, timestamp),
+    }
+}
+
+fn serve() -> Result<(), Box<dyn Error>> {
     println!("Starting up!");
     let index_path = &CONFIG.index.path;
     let socket_addr = SocketAddr::new(CONFIG.net.ip, CONFIG.net.port);
-    if index_path.exists() {
+    let index_existed = index_path.exists();
+    if index_existed {
         println!("Using existing index at {}", index_path.display());
     } else {
         println!("Creating new index at configured path {}", index_path.display());
@@ -42,10 +69,19 @@ fn main() -> Result<(), Box<dyn Error>> {
     let database_path = &CONFIG.database.path;
     if database_path.is_file() {
         println!("Using existing database file at {}", database_path.display());
+        database::migrate(database_path)?;
     } else {
         println!("Creating new database at configured path {}", database_path.display());
         database::init(database_path.as_path())?; 
+        if index_existed {
+            println!("The index is not empty, use the rebuild command to restore its crates in the database");
+        }
+    }
+    println!("Loaded {} crates from the index", index::cache::load());
+    if CONFIG.names.public.source.is_some() {
+        println!("Loaded {} public crate names", name_policy::public::load()?);
     }
+    mirror::start();
 
     let listener = TcpListener::bind(socket_addr)?;
     println!("Binding to {socket_addr}");
@@ -62,7 +98,8 @@ fn main() -> Result<(), Box<dyn Error>> {
 }
 
 fn handle_connection(mut stream: TcpStream) -> IoResult<()> {
-    let buffer = BufReader::new(&mut stream);
+    // Unbuffered, so a body sent right after the headers stays in the stream for the handler
+    let buffer = BufReader::with_capacity(1, &mut stream);
     let request: String = buffer.lines()
         .map(Result::unwrap)
         .take_while(|l| !l.is_empty())
@@ -89,14 +126,23 @@ fn handle_request(request: Request, mut stream: TcpStream) -> IoResult<()> {
             download::handle(stream, &path)},
 
         (RequestMethod::Put, [rest @ .., "new"]) if rest==API_COMMON => handle_authorized(stream, headers, |s, _, a| publish::handle_publish_request(s, a)),
+        (RequestMethod::Put, [rest @ .., "validate"]) if rest==API_COMMON => handle_authorized(stream, headers, |s, _, a| publish::handle_validate_request(s, a)),
 
         (RequestMethod::Put, [rest @ .., crate_name, version, "unyank"]) if rest == API_COMMON => handle_authorized(stream, headers, |s, _, a| yank::unyank(s, crate_name, version, a)),
-        (RequestMethod::Delete, [rest @ .., crate_name, version, "yank"]) if rest == API_COMMON => handle_authorized(stream, headers, |s, _, a| yank::yank(s, crate_name, version, a)),
+        (RequestMethod::Delete, [rest @ .., crate_name, version, "yank"]) if rest == API_COMMON => handle_authorized(stream, headers, |s, h, a| yank::yank(s, crate_name, version, &h, a)),
 
         (RequestMethod::Get, [rest @ .., crate_name, "owners"]) if rest == API_COMMON => handle_authorized(stream, headers, |s, _, a| owners::list(s, crate_name, a)),
         (RequestMethod::Put, [rest @ .., crate_name, "owners"]) if rest == API_COMMON => handle_authorized(stream, headers, |s, h, a| owners::add(s, crate_name, &h, a)),
         (RequestMethod::Delete, [rest @ .., crate_name, "owners"])if rest == API_COMMON => handle_authorized(stream, headers, |s, h, a| owners::remove(s, crate_name, &h, a)),
 
+        (RequestMethod::Get, [rest @ .., crate_name]) if rest == API_COMMON => crate_info::show_crate(stream, crate_name),
+        (RequestMethod::Get, [rest @ .., crate_name, "reverse_dependencies"]) if rest == API_COMMON => crate_info::show_reverse_dependencies(stream, crate_name),
+        (RequestMethod::Get, [rest @ .., crate_name, query]) if rest == API_COMMON && query.starts_with("index?") => history::handle_entries_request(stream, crate_name, query.strip_prefix("index?").unwrap()),
+        (RequestMethod::Get, [rest @ .., crate_name, version]) if rest == API_COMMON => crate_info::show_version(stream, crate_name, version),
+
+        (RequestMethod::Get, ["api", "v1", "index", "mirrors"]) => mirror::handle_status_request(stream),
+        (RequestMethod::Get, ["api", "v1", "index", "at", commit, file @ ..]) => history::handle_snapshot_request(stream, commit, file),
+        (RequestMethod::Get, [""]) => web::listing(stream),
         (RequestMethod::Get, ["api", "v1", query]) if query.starts_with("crates?") => search::handle_search_request(stream, query.strip_prefix("crates").unwrap()),
         (method, _) => {
             println!("Unrecognized {method:?} request for {path}");
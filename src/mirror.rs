@@ -0,0 +1,189 @@
+use std::{
+    net::TcpStream,
+    io::{Write, Result as IoResult},
+    sync::{mpsc::{self, Receiver, Sender, RecvTimeoutError}, LazyLock, Mutex, OnceLock},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::CONFIG,
+    git,
+    http::{Response, Byteable},
+};
+
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_mins(5);
+
+static STATUS: LazyLock<Mutex<Vec<RemoteStatus>>> = LazyLock::new(|| {
+    Mutex::new(CONFIG.index.remotes.iter().map(|r| RemoteStatus::new(r)).collect())
+});
+static SENDER: OnceLock<Sender<()>> = OnceLock::new();
+
+/// Starts the background thread pushing the index to all configured remotes.
+/// Does nothing if no remotes are configured.
+pub(crate) fn start() {
+    if CONFIG.index.remotes.is_empty() {
+        return;
+    }
+    let (sender, receiver) = mpsc::channel();
+    if SENDER.set(sender).is_err() {
+        return;
+    }
+    println!("Mirroring index to {}", CONFIG.index.remotes.join(", "));
+    thread::spawn(move || worker(&receiver));
+    // Push whatever has been committed while the server was down
+    notify();
+}
+
+/// Tells the background thread that there are new commits to push
+pub(crate) fn notify() {
+    if let Some(sender) = SENDER.get() {
+        // The worker only stops if it panicked, which it already reported
+        let _ = sender.send(());
+    }
+}
+
+pub(crate) fn handle_status_request(mut stream: TcpStream) -> IoResult<()> {
+    let head = git::head_commit(&CONFIG.index.path).ok();
+    let remotes = STATUS.lock().expect("mirror worker panicked").clone();
+    let remotes = remotes.into_iter()
+        .map(|status| RemoteLag {
+            lag: git::commits_since(&CONFIG.index.path, status.pushed_commit.as_deref()).ok(),
+            status,
+        })
+        .collect();
+    let response = Response::new(200).body(serde_json::to_string(&StatusJson { head, remotes })?);
+    stream.write_all(&response.into_bytes())
+}
+
+fn worker(receiver: &Receiver<()>) {
+    let mut retry_in = None;
+    loop {
+        let signal = match retry_in {
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(backoff) => receiver.recv_timeout(backoff),
+        };
+        if let Err(RecvTimeoutError::Disconnected) = signal {
+            return;
+        }
+        // Several commits in a row only need one push
+        while receiver.try_recv().is_ok() {}
+        retry_in = push_pending();
+    }
+}
+
+/// Pushes HEAD to every remote that does not have it yet.
+/// Returns the time to wait before retrying if any push failed.
+fn push_pending() -> Option<Duration> {
+    let head = match git::head_commit(&CONFIG.index.path) {
+        Ok(head) => head,
+        Err(e) => {
+            println!("Could not determine index HEAD for mirroring: {e}");
+            return Some(MAX_BACKOFF);
+        }
+    };
+    let pending: Vec<String> = STATUS.lock().expect("status lock poisoned")
+        .iter()
+        .filter(|s| s.pushed_commit.as_ref() != Some(&head))
+        .map(|s| s.remote.clone())
+        .collect();
+
+    let results: Vec<_> = pending.into_iter()
+        .map(|remote| {
+            let result = git::push(&CONFIG.index.path, &remote);
+            (remote, result)
+        })
+        .collect();
+
+    let mut status = STATUS.lock().expect("status lock poisoned");
+    for (remote, result) in results {
+        let Some(entry) = status.iter_mut().find(|s| s.remote == remote) else {
+            continue;
+        };
+        match result {
+            Ok(()) => {
+                entry.pushed_commit = Some(head.clone());
+                entry.last_success = Some(unix_time());
+                entry.failed_attempts = 0;
+                entry.last_error = None;
+            },
+            Err(e) => {
+                println!("Pushing index to {remote} failed: {e}");
+                entry.failed_attempts += 1;
+                entry.last_error = Some(e.to_string());
+            }
+        }
+    }
+    status.iter()
+        .filter(|s| s.failed_attempts > 0)
+        .map(|s| backoff(s.failed_attempts))
+        .min()
+}
+
+fn backoff(failed_attempts: u32) -> Duration {
+    BASE_BACKOFF
+        .saturating_mul(2_u32.saturating_pow(failed_attempts.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct RemoteStatus {
+    remote: String,
+    pushed_commit: Option<String>,
+    /// Seconds since the unix epoch
+    last_success: Option<u64>,
+    failed_attempts: u32,
+    last_error: Option<String>,
+}
+
+impl RemoteStatus {
+    fn new(remote: &str) -> Self {
+        Self {
+            remote: remote.to_string(),
+            pushed_commit: None,
+            last_success: None,
+            failed_attempts: 0,
+            last_error: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct StatusJson {
+    head: Option<String>,
+    remotes: Vec<RemoteLag>,
+}
+
+#[derive(Serialize)]
+struct RemoteLag {
+    #[serde(flatten)]
+    status: RemoteStatus,
+    /// Commits in the index that have not been pushed to this remote yet
+    lag: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{backoff, MAX_BACKOFF};
+
+    #[test]
+    fn backoff_doubles() {
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff(4), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(backoff(20), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::{NamePolicyConfig, TyposquattingMode};
+
+use self::error::NamePolicyError;
+
+pub(crate) mod error;
+pub(crate) mod public;
+mod typosquatting;
+
+/// Keywords of all editions, a crate with such a name cannot be used with `extern crate`
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct",
+    "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while",
+    "yield",
+];
+
+/// Device names Windows does not allow as file names, crates with them cannot be unpacked there
+const WINDOWS_RESERVED: &[&str] = &[
+    "con", "prn", "aux", "nul",
+    "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Checks whether the actor authorized as `auth` may publish the first version of a crate named `name`
+pub(crate) fn check(name: &str, auth: &str, policy: &NamePolicyConfig) -> Result<(), NamePolicyError> {
+    let normalized = normalize(name);
+    if RUST_KEYWORDS.contains(&name.to_lowercase().as_str()) {
+        return Err(NamePolicyError::RustKeyword);
+    }
+    if WINDOWS_RESERVED.contains(&normalized.as_str()) {
+        return Err(NamePolicyError::WindowsReserved);
+    }
+    if policy.reserved.iter().any(|reserved| normalize(reserved) == normalized) {
+        return Err(NamePolicyError::Reserved);
+    }
+    public::check(&normalized, &policy.public)?;
+    if let Some(pattern) = policy.blocklist.iter().find(|pattern| pattern.is_match(name)) {
+        return Err(NamePolicyError::Blocked(pattern.to_string()));
+    }
+    // The most specific prefix decides, so `payments-internal-` can belong to another team than `payments-`
+    let owning_prefix = policy.prefixes.iter()
+        .filter(|(prefix, _)| normalized.starts_with(&normalize(prefix)))
+        .max_by_key(|(prefix, _)| prefix.len());
+    if let Some((prefix, team)) = owning_prefix {
+        let is_member = policy.teams.get(team).is_some_and(|members| members.iter().any(|member| member == auth));
+        if !is_member {
+            return Err(NamePolicyError::PrefixReserved { prefix: prefix.clone(), team: team.clone() });
+        }
+    }
+    Ok(())
+}
+
+/// Names out of `existing` a new crate called `name` could be mistaken for, to be shown as warnings.
+/// Fails instead if the policy blocks such names.
+pub(crate) fn similar_existing_names(name: &str, existing: &[String], policy: &NamePolicyConfig) -> Result<Vec<String>, NamePolicyError> {
+    if policy.typosquatting.mode == TyposquattingMode::Off {
+        return Ok(vec![]);
+    }
+    let similar = typosquatting::similar_names(name, existing.iter().map(String::as_str), &policy.typosquatting);
+    match similar.first() {
+        Some(first) if policy.typosquatting.mode == TyposquattingMode::Block => Err(NamePolicyError::TooSimilar(first.clone())),
+        _ => Ok(similar),
+    }
+}
+
+/// Crate names differing only in case or dashes and underscores count as the same
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+    use super::{check, error::NamePolicyError};
+    use crate::config::NamePolicyConfig;
+
+    fn policy() -> NamePolicyConfig {
+        NamePolicyConfig {
+            reserved: vec!["std-extras".to_string()],
+            blocklist: vec![Regex::new("^rustup").unwrap()],
+            teams: [
+                ("payments".to_string(), vec!["alice".to_string()]),
+                ("ledger".to_string(), vec!["bob".to_string()]),
+            ].into(),
+            prefixes: [
+                ("payments-".to_string(), "payments".to_string()),
+                ("payments-ledger-".to_string(), "ledger".to_string()),
+            ].into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fixed_names() {
+        assert_eq!(check("async", "alice", &policy()), Err(NamePolicyError::RustKeyword));
+        assert_eq!(check("NUL", "alice", &policy()), Err(NamePolicyError::WindowsReserved));
+        assert_eq!(check("com1", "alice", &policy()), Err(NamePolicyError::WindowsReserved));
+        assert!(check("console", "alice", &policy()).is_ok());
+    }
+
+    #[test]
+    fn configured_names() {
+        assert_eq!(check("std_extras", "alice", &policy()), Err(NamePolicyError::Reserved));
+        assert!(matches!(check("rustup-helper", "alice", &policy()), Err(NamePolicyError::Blocked(_))));
+        assert!(check("helper-rustup", "alice", &policy()).is_ok());
+    }
+
+    #[test]
+    fn prefixes_belong_to_teams() {
+        assert!(check("payments-api", "alice", &policy()).is_ok());
+        assert!(check("payments_api", "alice", &policy()).is_ok());
+        assert!(matches!(check("payments-api", "bob", &policy()),
+            Err(NamePolicyError::PrefixReserved { team, .. }) if team == "payments"));
+        assert!(check("payments-ledger-core", "bob", &policy()).is_ok());
+        assert!(check("payments-ledger-core", "alice", &policy()).is_err());
+        assert!(check("paymentsapi", "bob", &policy()).is_ok());
+    }
+}
@@ -0,0 +1,31 @@
+use std::{
+    error::Error,
+    fmt::{Formatter, Display, Result as FMTResult},
+};
+
+#[derive(Debug, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub(crate) enum NamePolicyError {
+    Reserved,
+    RustKeyword,
+    WindowsReserved,
+    Blocked(String),
+    PrefixReserved { prefix: String, team: String },
+    PublicName { can_override: bool },
+    TooSimilar(String),
+}
+impl Error for NamePolicyError {}
+impl Display for NamePolicyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FMTResult {
+        match self {
+            Self::Reserved => write!(f, "the crate name is reserved by this registry"),
+            Self::RustKeyword => write!(f, "the crate name is a Rust keyword"),
+            Self::WindowsReserved => write!(f, "the crate name is a reserved file name on Windows"),
+            Self::Blocked(pattern) => write!(f, "the crate name matches the blocked pattern {pattern}"),
+            Self::PublicName { can_override: false } => write!(f, "a crate with this name exists on crates.io, dependents could resolve to the wrong one"),
+            Self::PublicName { can_override: true } => write!(f, "a crate with this name exists on crates.io, it can only be published after adding it to the public name overrides"),
+            Self::TooSimilar(existing) => write!(f, "the crate name is too similar to the existing crate {existing}"),
+            Self::PrefixReserved { prefix, team } => write!(f, "crate names starting with {prefix} can only be published by the {team} team"),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::HashSet,
+    io::Result as IoResult,
+    path::Path,
+    sync::OnceLock,
+};
+
+use walkdir::WalkDir;
+
+use crate::config::{CONFIG, PublicNameMode, PublicNamesConfig};
+
+use super::{error::NamePolicyError, normalize};
+
+/// Normalized names of all crates in the configured public name list, set by `load`
+static PUBLIC_NAMES: OnceLock<HashSet<String>> = OnceLock::new();
+
+/// Reads the public name list into memory if that did not happen yet and returns the number of names
+pub(crate) fn load() -> IoResult<usize> {
+    if let Some(names) = PUBLIC_NAMES.get() {
+        return Ok(names.len());
+    }
+    let names = match &CONFIG.names.public.source {
+        Some(source) => read_names(source)?,
+        None => HashSet::new(),
+    };
+    Ok(PUBLIC_NAMES.get_or_init(|| names).len())
+}
+
+/// Whether a new crate called `normalized` would shadow a public crate, given the configured exceptions.
+/// Nothing is shadowed before the list was loaded
+pub(super) fn check(normalized: &str, config: &PublicNamesConfig) -> Result<(), NamePolicyError> {
+    match PUBLIC_NAMES.get() {
+        Some(public_names) => check_against(normalized, config, public_names),
+        None => Ok(()),
+    }
+}
+
+fn check_against(normalized: &str, config: &PublicNamesConfig, public_names: &HashSet<String>) -> Result<(), NamePolicyError> {
+    if !public_names.contains(normalized) {
+        return Ok(());
+    }
+    match config.mode {
+        PublicNameMode::Override if config.overrides.iter().any(|name| normalize(name) == normalized) => Ok(()),
+        mode => Err(NamePolicyError::PublicName { can_override: mode == PublicNameMode::Override }),
+    }
+}
+
+/// In an index directory every file except `config.json` is named after its crate,
+/// any other file holds one name per line with `#` starting a comment line
+fn read_names(source: &Path) -> IoResult<HashSet<String>> {
+    if source.is_dir() {
+        let mut names = HashSet::new();
+        for entry in WalkDir::new(source).into_iter().filter_entry(|e| e.file_name() != ".git") {
+            let entry = entry?;
+            if entry.file_type().is_file() && entry.file_name() != "config.json" {
+                names.insert(normalize(&entry.file_name().to_string_lossy()));
+            }
+        }
+        return Ok(names);
+    }
+    Ok(std::fs::read_to_string(source)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(normalize)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::{check_against, read_names};
+    use crate::{config::{PublicNameMode, PublicNamesConfig}, name_policy::error::NamePolicyError};
+
+    #[test]
+    fn reads_name_file_and_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("names.txt");
+        std::fs::write(&file, "# crates.io\nSerde\n\n  rand_core \n").unwrap();
+        assert_eq!(read_names(&file).unwrap(), HashSet::from(["serde".to_string(), "rand_core".to_string()]));
+
+        let index = dir.path().join("index");
+        std::fs::create_dir_all(index.join("se/rd")).unwrap();
+        std::fs::create_dir_all(index.join(".git")).unwrap();
+        std::fs::write(index.join("se/rd/serde-json"), "").unwrap();
+        std::fs::write(index.join("config.json"), "{}").unwrap();
+        std::fs::write(index.join(".git/HEAD"), "").unwrap();
+        assert_eq!(read_names(&index).unwrap(), HashSet::from(["serde_json".to_string()]));
+    }
+
+    #[test]
+    fn modes() {
+        let public = HashSet::from(["serde".to_string()]);
+        let mut config = PublicNamesConfig { overrides: vec!["serde".to_string()], ..Default::default() };
+        assert!(check_against("internal_thing", &config, &public).is_ok());
+        assert_eq!(check_against("serde", &config, &public), Err(NamePolicyError::PublicName { can_override: false }));
+        config.mode = PublicNameMode::Override;
+        assert!(check_against("serde", &config, &public).is_ok());
+        config.overrides.clear();
+        assert_eq!(check_against("serde", &config, &public), Err(NamePolicyError::PublicName { can_override: true }));
+    }
+}
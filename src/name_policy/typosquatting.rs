@@ -0,0 +1,83 @@
+use crate::config::TyposquattingConfig;
+
+use super::normalize;
+
+/// Below this length nearly every name is one edit away from another, so only confusables count
+const MIN_LENGTH_FOR_DISTANCE: usize = 4;
+
+/// Pairs of character sequences that look alike in many fonts, the first is replaced by the second
+const CONFUSABLES: &[(&str, &str)] = &[("rn", "m"), ("vv", "w"), ("0", "o"), ("1", "l"), ("i", "l"), ("5", "s")];
+
+/// Existing crate names `name` could be mistaken for, leaving out the crate itself
+pub(crate) fn similar_names<'a>(name: &str, existing: impl IntoIterator<Item = &'a str>, config: &TyposquattingConfig) -> Vec<String> {
+    let normalized = normalize(name);
+    let skeleton = skeleton(&normalized);
+    existing.into_iter()
+        .filter(|other| {
+            let other_normalized = normalize(other);
+            if other_normalized == normalized {
+                return false;
+            }
+            skeleton == self::skeleton(&other_normalized)
+                || (normalized.len().min(other_normalized.len()) >= MIN_LENGTH_FOR_DISTANCE
+                    && distance(&normalized, &other_normalized) <= config.max_distance)
+        })
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// A form in which confusable characters and separators no longer make a difference
+fn skeleton(normalized: &str) -> String {
+    let mut skeleton = normalized.replace('_', "");
+    for (from, to) in CONFUSABLES {
+        skeleton = skeleton.replace(from, to);
+    }
+    skeleton
+}
+
+/// Edit distance counting swaps of neighbouring characters as one edit
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{distance, similar_names};
+    use crate::config::TyposquattingConfig;
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("serde", "serde"), 0);
+        assert_eq!(distance("serde", "sedre"), 1);
+        assert_eq!(distance("serde", "serd"), 1);
+        assert_eq!(distance("serde", "serve"), 1);
+        assert_eq!(distance("tokio", "tokoi_x"), 3);
+    }
+
+    #[test]
+    fn finds_similar_names() {
+        let existing = ["serde", "serde-json", "tokio", "log", "rand", "clap"];
+        let similar = |name| similar_names(name, existing, &TyposquattingConfig::default());
+        assert_eq!(similar("sedre"), ["serde"]);
+        assert_eq!(similar("serdejson"), ["serde-json"]);
+        assert_eq!(similar("t0ki0"), ["tokio"]);
+        assert_eq!(similar("1og"), ["log"]);
+        assert_eq!(similar("cIap"), ["clap"]);
+        assert!(similar("lag").is_empty());
+        assert!(similar("serde_json").is_empty());
+        assert!(similar("actix").is_empty());
+    }
+}
@@ -28,7 +28,7 @@ pub fn add(mut stream: TcpStream, crate_name: &str, headers: &HashMap<String, St
     };
     println!("OWNER ADD {crate_name} [{auth}]");
     match users.iter().try_for_each(|u| database::add_owner(crate_name, u)) {
-        Ok(_) => {},
+        Ok(()) => {},
         Err(e) => {
             let code = match &e {
                 AddOwnerError::MultipleUsers => 403,
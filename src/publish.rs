@@ -1,16 +1,18 @@
 use std::{
-    io::{Read, Write, Result as IoResult},
+    io::{Read, Write, Result as IoResult, ErrorKind},
     net::TcpStream, 
-    collections::HashMap, 
-    path::PathBuf, 
-    fs::{OpenOptions, File},
+    collections::{HashMap, BTreeMap}, 
+    path::{Path, PathBuf}, 
+    fs::File,
+    ops::Deref,
 };
 use crate::{
-    index::{IndexCrate, self}, 
-    dependency::Dependency, 
+    index::{IndexCrate, self, cache::IndexCache}, 
+    dependency::{Dependency, policy}, 
     git::add_and_commit_to_index,  
     error::ReturnJson as ErrorJson, 
-    config::CONFIG, database,
+    config::{CONFIG, Config, PublishConfig, DependencyPolicyMode}, database, download, crate_file, features, license, version,
+    name_policy::{self, error::NamePolicyError},
     http::{Response, Byteable},
 };
 use serde::{Deserialize, Serialize, de::Error};
@@ -20,7 +22,18 @@ use self::error::{PublishError, ReadStreamError};
 pub mod error;
 type PublishResult<T> = core::result::Result<T, PublishError>;
 
-pub(crate) fn handle_publish_request(mut stream: TcpStream, auth: &str) -> IoResult<()> {
+/// `PUT /api/v1/crates/new`
+pub(crate) fn handle_publish_request(stream: TcpStream, auth: &str) -> IoResult<()> {
+    handle(stream, auth, false)
+}
+
+/// `PUT /api/v1/crates/validate`, taking the same body as a publish and answering like one,
+/// but only running the checks without storing anything
+pub(crate) fn handle_validate_request(stream: TcpStream, auth: &str) -> IoResult<()> {
+    handle(stream, auth, true)
+}
+
+fn handle(mut stream: TcpStream, auth: &str, dry_run: bool) -> IoResult<()> {
     let (published_crate, raw_crate_file) = match get_crate_and_raw_bytes_from_stream(&mut stream) {
         Ok(t) => t,
         Err(e) => {
@@ -37,22 +50,31 @@ pub(crate) fn handle_publish_request(mut stream: TcpStream, auth: &str) -> IoRes
             return stream.write_all(&response.into_bytes())
         }
     };
-    println!("PUBLISH {} v{} [{auth}]", published_crate.name, published_crate.vers);
-    
-    match process_publish_request(&published_crate, &raw_crate_file) {
-        Ok(_) => {
-            let warnings_json = serde_json::to_string(
-                &ReturnJson::new()).expect("This is a static json object");
+    println!("{} {} v{} [{auth}]", if dry_run { "VALIDATE" } else { "PUBLISH" }, published_crate.name, published_crate.vers);
+
+    let result = if dry_run {
+        validate(&published_crate, &raw_crate_file, auth, &CONFIG, index::cache::read)
+    } else {
+        process_publish_request(&published_crate, &raw_crate_file, auth)
+    };
+    match result {
+        Ok(policy_warnings) => {
+            let mut warnings = PublishWarnings::new(&published_crate, &CONFIG.publish);
+            warnings.other.extend(policy_warnings);
+            let warnings_json = serde_json::to_string(&ReturnJson { warnings }).expect("This is a static json object");
             Ok(stream.write_all(&Response::new(200).body(warnings_json).into_bytes())?)
         },
         Err(pub_err) => {
             use PublishError::{
                 BadIndexJson, CrateExistsWithDifferentDashUnderscore, 
-                IoError, SerializationFailed, VersionAlreadyExists
+                IoError, SerializationFailed, VersionAlreadyExists, InvalidCrateFile, SqlError, NamePolicy,
+                DependencyPolicy, InvalidFeatures, InvalidLicense
             };
             let code = match pub_err {
-                VersionAlreadyExists | CrateExistsWithDifferentDashUnderscore => 403,
-                IoError(_) | BadIndexJson | SerializationFailed(_) => 500,
+                VersionAlreadyExists | CrateExistsWithDifferentDashUnderscore
+                    | NamePolicy(NamePolicyError::PrefixReserved { .. }) => 403,
+                InvalidCrateFile(_) | NamePolicy(_) | DependencyPolicy(_) | InvalidFeatures(_) | InvalidLicense(_) => 400,
+                IoError(_) | BadIndexJson | SerializationFailed(_) | SqlError(_) => 500,
             };
             let response = Response::new(code).body(ErrorJson::new(&[pub_err]));
             stream.write_all(&response.into_bytes())
@@ -60,45 +82,164 @@ pub(crate) fn handle_publish_request(mut stream: TcpStream, auth: &str) -> IoRes
     }
 }
 
-fn process_publish_request(package: &PublishedPackage, raw_file_bytes: &[u8]) -> PublishResult<()> {
+/// Runs every check of a publish against the crates returned by `cache` without changing anything.
+/// The cache is only requested once the crate file was checked, so writers are not blocked meanwhile.
+/// Returns the warnings of the license, name and dependency policies.
+fn validate<C, F>(package: &PublishedPackage, raw_file_bytes: &[u8], auth: &str, config: &Config, cache: F) -> PublishResult<Vec<String>>
+    where C: Deref<Target = IndexCache>, F: FnOnce() -> C {
+    let mut warnings = check_package(package, raw_file_bytes, config)?;
+    warnings.extend(check_against_registry(package, auth, config, &cache())?);
+    Ok(warnings)
+}
+
+/// Checks that only depend on the published package itself
+fn check_package(package: &PublishedPackage, raw_file_bytes: &[u8], config: &Config) -> PublishResult<Vec<String>> {
+    crate_file::validate(raw_file_bytes, &package.name, &package.vers)?;
+    let feature_errors = features::validate(&package.features, &package.deps);
+    if !feature_errors.is_empty() {
+        return Err(PublishError::InvalidFeatures(feature_errors));
+    }
+    let license_warning = license::check(package.license.as_deref(), package.license_file.as_deref(), &config.licenses)?;
+    Ok(license_warning.into_iter().collect())
+}
+
+/// Checks against the crates already in the index, which must not change until publishing is done
+fn check_against_registry(package: &PublishedPackage, auth: &str, config: &Config, cache: &IndexCache) -> PublishResult<Vec<String>> {
+    let existing_versions = cache.versions(&package.name).unwrap_or_default();
+    for existing in existing_versions {
+        if existing.name != package.name {
+            return Err(PublishError::CrateExistsWithDifferentDashUnderscore)
+        } else if version::same_release(&existing.vers, &package.vers) {
+            return Err(PublishError::VersionAlreadyExists)
+        }
+    }
+    let mut warnings = vec![];
+    // Name rules only apply to new crates, so tightening them never locks out existing ones
+    if existing_versions.is_empty() {
+        name_policy::check(&package.name, auth, &config.names)?;
+        let similar_names = name_policy::similar_existing_names(&package.name, &cache.names(), &config.names)?;
+        warnings.extend(similar_names.iter().map(|name| format!("the name is similar to the existing crate \"{name}\"")));
+    }
+    let dependency_violations = policy::violations(&package.deps, &config.dependencies, |name| cache.versions(name).map(<[IndexCrate]>::to_vec));
+    match config.dependencies.mode {
+        DependencyPolicyMode::Deny if !dependency_violations.is_empty() => return Err(PublishError::DependencyPolicy(dependency_violations)),
+        _ => warnings.extend(dependency_violations.iter().map(ToString::to_string)),
+    }
+    Ok(warnings)
+}
+
+/// Stores the version in the database, the index, the git history and the download directory.
+/// Either all of these succeed or every change already made is undone.
+/// Returns the warnings of the license, name and dependency policies.
+fn process_publish_request(package: &PublishedPackage, raw_file_bytes: &[u8], auth: &str) -> PublishResult<Vec<String>> {
+    let mut warnings = check_package(package, raw_file_bytes, &CONFIG)?;
     let index_crate = IndexCrate::new(package.clone(), raw_file_bytes);
-    // Check for existing version
-    let index_file_path_absolute = &CONFIG.index.path.join(index_crate.path_in_index());
-    for index_crate_res in index::walk_index_crates() {
-        let index_crate_in_file = index_crate_res?;
-        if index_crate_in_file.name.replace('-', "_") == index_crate.name.replace('-', "_") {
-            if index_crate_in_file.name != index_crate.name {
-                return Err(PublishError::CrateExistsWithDifferentDashUnderscore)
-            } else if index_crate_in_file.vers == index_crate.vers {
-                return Err(PublishError::VersionAlreadyExists)
-            }
+    let _index_lock = index::WRITE_LOCK.lock().expect("index writer panicked");
+    warnings.extend(check_against_registry(package, auth, &CONFIG, &index::cache::read())?);
+
+    // Rolled back when dropped without commit
+    let changes = database::begin()?;
+    changes.add_package(package, &index_crate.cksum)?;
+
+    let crate_file = StagedFile::write(download::crate_file_path(&index_crate.name, &index_crate.vers), raw_file_bytes)?;
+
+    let index_file_path_relative = index_crate.path_in_index();
+    let index_file_path_absolute = CONFIG.index.path.join(&index_file_path_relative);
+    let previous_index_file = match std::fs::read(&index_file_path_absolute) {
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        content => Some(content?),
+    };
+    if let Some(parent) = index_file_path_absolute.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut index_file = previous_index_file.clone().unwrap_or_default();
+    writeln!(index_file, "{}", index_crate.index_line()?)?;
+    index::write_file_atomically(&index_file_path_absolute, &index_file)?;
+
+    let message = format!("Add package [{}] version [{}] to index", index_crate.name, index_crate.vers);
+    let crate_file_path = match crate_file.persist().and_then(|path| {
+        add_and_commit_to_index(&index_file_path_relative, &message)?;
+        Ok(path)
+    }) {
+        Ok(path) => path,
+        Err(e) => {
+            restore_index_file(&index_file_path_absolute, previous_index_file.as_deref());
+            remove_crate_file(&download::crate_file_path(&index_crate.name, &index_crate.vers));
+            return Err(e.into());
+        }
+    };
+
+    if let Err(e) = changes.commit() {
+        // The commit is already in the history and maybe pushed, so it is reverted by another one
+        restore_index_file(&index_file_path_absolute, previous_index_file.as_deref());
+        remove_crate_file(&crate_file_path);
+        add_and_commit_to_index(&index_file_path_relative, &format!("Revert adding package [{}] version [{}]", index_crate.name, index_crate.vers))?;
+        return Err(e.into());
+    }
+    index::cache::insert(index_crate);
+    Ok(warnings)
+}
+
+/// Puts the index file back into the state before publishing, deleting it if it did not exist
+fn restore_index_file(path: &Path, previous: Option<&[u8]>) {
+    let result = match previous {
+        Some(content) => index::write_file_atomically(path, content),
+        None => std::fs::remove_file(path).map(|()| remove_empty_parents(path, &CONFIG.index.path)),
+    };
+    if let Err(e) = result {
+        println!("Failed to restore index file {}: {e}", path.display());
+    }
+}
+
+fn remove_crate_file(path: &Path) {
+    if std::fs::remove_file(path).is_ok() {
+        remove_empty_parents(path, Path::new(&CONFIG.download.path));
+    }
+}
+
+/// Removes the directories containing `path` up to `root` as long as they are empty
+fn remove_empty_parents(path: &Path, root: &Path) {
+    for directory in path.ancestors().skip(1).take_while(|d| *d != root) {
+        if std::fs::remove_dir(directory).is_err() {
+            break;
         }
     }
+}
 
-    database::add_package(package).unwrap();
+/// A file written next to its final location, removed again unless it is persisted
+struct StagedFile {
+    staged: PathBuf,
+    target: PathBuf,
+    persisted: bool,
+}
 
-    if !index_file_path_absolute.exists() {
-        if let Some(parent) = index_file_path_absolute.parent() {
+impl StagedFile {
+    fn write(target: PathBuf, content: &[u8]) -> IoResult<Self> {
+        if let Some(parent) = target.parent() {
             std::fs::create_dir_all(parent)?;
         }
+        let mut name = target.file_name().unwrap_or_default().to_os_string();
+        name.push(".staged");
+        let staged = target.with_file_name(name);
+        let this = Self { staged, target, persisted: false };
+        write_file(&this.staged, content)?;
+        Ok(this)
     }
-    
-    let mut index_file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(index_file_path_absolute)?;
 
-    write!(index_file, "{}\r\n", serde_json::to_string(&index_crate)?)?;
-    drop(index_file);
-
-    // Put file into index folder
-    let crate_file_path = PathBuf::from(format!("{}/{}/{}/download", CONFIG.download.path, index_crate.name.to_lowercase(), index_crate.vers));
-    if let Some(parent) = crate_file_path.parent() {
-        std::fs::create_dir_all(parent)?;
+    /// Moves the file to its final location and returns that
+    fn persist(mut self) -> IoResult<PathBuf> {
+        std::fs::rename(&self.staged, &self.target)?;
+        self.persisted = true;
+        Ok(self.target.clone())
     }
-    write_file(&crate_file_path, raw_file_bytes)?;
+}
 
-    Ok(add_and_commit_to_index(&index_crate.path_in_index(), &format!("Add package [{}] version [{}] to index", index_crate.name, index_crate.vers))?)
+impl Drop for StagedFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = std::fs::remove_file(&self.staged);
+        }
+    }
 }
 
 fn get_crate_and_raw_bytes_from_stream(stream: &mut TcpStream) -> Result<(PublishedPackage, Vec<u8>), ReadStreamError> {
@@ -121,7 +262,7 @@ fn get_crate_and_raw_bytes_from_stream(stream: &mut TcpStream) -> Result<(Publis
     Ok((parsed_json, raw_crate_file))
 }
 
-fn write_file(path: &PathBuf, raw_bytes: &[u8]) -> PublishResult<()> {
+fn write_file(path: &Path, raw_bytes: &[u8]) -> IoResult<()> {
     let mut target_file = File::create(path)?;
     target_file.write_all(raw_bytes)?;
     println!("Wrote file to {}", path.display());
@@ -147,8 +288,14 @@ pub(crate) struct PublishedPackage {
     pub(crate) license: Option<String>,
     pub(crate) license_file: Option<String>,
     pub(crate) repository: Option<String>,
-    pub(crate) badges: HashMap<String, String>,
+    /// Badge name to badge attributes, like `{"maintenance": {"status": "experimental"}}`
+    pub(crate) badges: HashMap<String, HashMap<String, String>>,
     pub(crate) links: Option<String>,
+    #[serde(default)]
+    pub(crate) rust_version: Option<String>,
+    /// Fields this registry does not know, reported back as warnings
+    #[serde(flatten)]
+    pub(crate) unknown_fields: BTreeMap<String, serde_json::Value>,
 }
 
 impl<'de> Deserialize<'de> for PublishedPackage {
@@ -159,44 +306,259 @@ impl<'de> Deserialize<'de> for PublishedPackage {
 
         if !this.name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
                 return Err(D::Error::custom("non-alphanumeric or -/_ characters allowed!"));
-        };
+        }
         
         match this.name.chars().next() {
             Some(c) if !c.is_alphabetic() => return Err(D::Error::custom("first character in name must be alphabetic!")),
             None => return Err(D::Error::custom("empty crate name not allowed!")),
             _ => {}
-        };
+        }
 
         if this.name.chars().count() > 64 {
             return Err(D::Error::custom("crate name is too long!"))
         }
+
+        if let Err(e) = semver::Version::parse(&this.vers) {
+            return Err(D::Error::custom(format!("version \"{}\" is not a valid semver version: {e}", this.vers)));
+        }
+
+        if this.keywords.len() > MAX_KEYWORDS {
+            return Err(D::Error::custom(format!("expected at most {MAX_KEYWORDS} keywords!")))
+        }
+
+        if let Some(keyword) = this.keywords.iter().find(|k| !is_valid_keyword(k)) {
+            return Err(D::Error::custom(format!("invalid keyword \"{keyword}\": keywords have at most {MAX_KEYWORD_LENGTH} characters, \
+                start with a letter or digit and only contain letters, digits, _, - or +!")))
+        }
+
+        if this.categories.len() > MAX_CATEGORIES {
+            return Err(D::Error::custom(format!("expected at most {MAX_CATEGORIES} categories!")))
+        }
+
+        if let Some(rust_version) = &this.rust_version {
+            if !is_valid_rust_version(rust_version) {
+                return Err(D::Error::custom("rust_version must be a version like \"1.70\" without pre-release or build metadata!"))
+            }
+        }
         
         this.name = this.name.to_ascii_lowercase();
         Ok(this)
     }
 }
 
+const MAX_KEYWORDS: usize = 5;
+const MAX_KEYWORD_LENGTH: usize = 20;
+const MAX_CATEGORIES: usize = 5;
+
+/// The same rules crates.io applies
+fn is_valid_keyword(keyword: &str) -> bool {
+    keyword.len() <= MAX_KEYWORD_LENGTH
+        && keyword.chars().next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && keyword.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+}
+
+/// Cargo only allows one to three numeric components, e.g. "1", "1.70" or "1.70.0"
+fn is_valid_rust_version(rust_version: &str) -> bool {
+    let components: Vec<_> = rust_version.split('.').collect();
+    components.len() <= 3 && components.iter()
+        .all(|c| !c.is_empty() && c.chars().all(|ch| ch.is_ascii_digit()))
+}
+
 #[derive(Serialize, Deserialize, Default)]
 struct ReturnJson {
     warnings: PublishWarnings
 }
-#[allow(dead_code)]
-impl ReturnJson {
-    fn with_others(self, others: Vec<String>) -> Self {
-        let mut res = self;
-        for o in others {
-            res.warnings.other.push(o);
-        }
-        res
-    }
-    fn new() -> Self {
-        Self::default()
-    }
-}
 
-#[derive(Serialize, Deserialize, Default)]
+/// Problems cargo shows to the user after an otherwise successful publish
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
 struct PublishWarnings {
     invalid_categories: Vec<String>,
     invalid_badges: Vec<String>,
     other: Vec<String>,
-}
\ No newline at end of file
+}
+
+impl PublishWarnings {
+    fn new(package: &PublishedPackage, config: &PublishConfig) -> Self {
+        let invalid_categories = package.categories.iter()
+            .filter(|c| !config.categories.contains(c))
+            .cloned()
+            .collect();
+        let mut invalid_badges: Vec<_> = package.badges.iter()
+            .filter(|(name, attributes)| !config.badges.get(*name).is_some_and(|schema| {
+                attributes.iter().all(|(attribute, value)| schema.get(attribute)
+                    .is_some_and(|accepted| accepted.is_empty() || accepted.contains(value)))
+            }))
+            .map(|(name, _)| name.clone())
+            .collect();
+        invalid_badges.sort();
+        let other = package.unknown_fields.keys()
+            .map(|field| format!("unknown field \"{field}\" was ignored"))
+            .collect();
+        Self { invalid_categories, invalid_badges, other }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+    use walkdir::WalkDir;
+
+    use crate::{
+        config::{Config, DatabaseConfig, DependencyPolicyConfig, DependencyPolicyMode, DownloadConfig, IndexConfig, NamePolicyConfig, PublishConfig},
+        crate_file::tests::build_crate_file,
+        database,
+        dependency::error::DependencyPolicyError,
+        index::{IndexCrate, cache::IndexCache},
+        name_policy::error::NamePolicyError,
+    };
+    use super::{PublishedPackage, PublishWarnings, StagedFile, error::PublishError, is_valid_rust_version, remove_empty_parents, validate};
+
+    pub(crate) fn package_json(name: &str, extra: &str) -> String {
+        format!(r#"{{"name":"{name}","vers":"0.1.0","deps":[],"features":{{}},"authors":[],
+            "description":null,"documentation":null,"homepage":null,"readme":null,"readme_file":null,
+            "keywords":[],"categories":[],"license":"MIT","license_file":null,"repository":null,
+            "badges":{{}},"links":null{extra}}}"#)
+    }
+
+    #[test]
+    fn rust_version_formats() {
+        assert!(is_valid_rust_version("1"));
+        assert!(is_valid_rust_version("1.70"));
+        assert!(is_valid_rust_version("1.70.0"));
+        assert!(!is_valid_rust_version("1.70.0.1"));
+        assert!(!is_valid_rust_version("1.70-beta"));
+        assert!(!is_valid_rust_version("1..0"));
+        assert!(!is_valid_rust_version(""));
+    }
+
+    #[test]
+    fn deserialize_rust_version() {
+        let package: PublishedPackage = serde_json::from_str(&package_json("foo", r#","rust_version":"1.65""#)).unwrap();
+        assert_eq!(package.rust_version.as_deref(), Some("1.65"));
+        let package: PublishedPackage = serde_json::from_str(&package_json("foo", "")).unwrap();
+        assert_eq!(package.rust_version, None);
+        assert!(serde_json::from_str::<PublishedPackage>(&package_json("foo", r#","rust_version":"stable""#)).is_err());
+    }
+
+    #[test]
+    fn deserialize_version() {
+        let package: PublishedPackage = serde_json::from_str(&package_json("foo", "").replace("0.1.0", "1.0.0-beta.1+build")).unwrap();
+        assert_eq!(package.vers, "1.0.0-beta.1+build");
+        assert!(serde_json::from_str::<PublishedPackage>(&package_json("foo", "").replace("0.1.0", "1.0")).is_err());
+    }
+
+    #[test]
+    fn deserialize_keywords() {
+        let package: PublishedPackage = serde_json::from_str(&package_json("foo", "").replace(r#""keywords":[]"#, r#""keywords":["c++","no_std","cli-tool"]"#)).unwrap();
+        assert_eq!(package.keywords.len(), 3);
+        for keywords in [r#"["a","b","c","d","e","f"]"#, r#"["-cli"]"#, r#"["with space"]"#, r#"["averyveryverylongkeyword"]"#] {
+            let json = package_json("foo", "").replace(r#""keywords":[]"#, &format!(r#""keywords":{keywords}"#));
+            assert!(serde_json::from_str::<PublishedPackage>(&json).is_err(), "{keywords} accepted");
+        }
+    }
+
+    #[test]
+    fn warnings_for_categories_badges_and_unknown_fields() {
+        let json = package_json("foo", r#","future_field":1"#)
+            .replace(r#""categories":[]"#, r#""categories":["parsing","made-up"]"#)
+            .replace(r#""badges":{}"#, r#""badges":{"maintenance":{"status":"experimental"},"travis-ci":{"repository":"a/b"},"is-it-maintained-issue-resolution":{}}"#);
+        let package: PublishedPackage = serde_json::from_str(&json).unwrap();
+        let mut config = PublishConfig::default();
+        config.badges.insert("travis-ci".to_string(), [("repository".to_string(), vec![])].into());
+        assert_eq!(PublishWarnings::new(&package, &config), PublishWarnings {
+            invalid_categories: vec!["made-up".to_string()],
+            invalid_badges: vec!["is-it-maintained-issue-resolution".to_string()],
+            other: vec![r#"unknown field "future_field" was ignored"#.to_string()],
+        });
+        let json = package_json("foo", "").replace(r#""badges":{}"#, r#""badges":{"maintenance":{"status":"abandoned"}}"#);
+        let package: PublishedPackage = serde_json::from_str(&json).unwrap();
+        assert_eq!(PublishWarnings::new(&package, &PublishConfig::default()).invalid_badges, ["maintenance"]);
+    }
+
+    fn package_and_file(name: &str, extra_deps: &str) -> (PublishedPackage, Vec<u8>) {
+        let json = package_json(name, "").replace(r#""deps":[]"#, &format!("\"deps\":[{extra_deps}]"));
+        let manifest = format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n");
+        let file = build_crate_file(&[(&format!("{name}-0.1.0/Cargo.toml"), manifest.as_bytes())]);
+        (serde_json::from_str(&json).unwrap(), file)
+    }
+
+    /// Every file below `root` with its content
+    fn files_below(root: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        WalkDir::new(root).into_iter().flatten()
+            .filter(|e| e.file_type().is_file())
+            .map(|e| (e.path().to_path_buf(), std::fs::read(e.path()).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn validate_matches_publish_checks_and_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            index: IndexConfig { path: dir.path().join("index"), ..Default::default() },
+            download: DownloadConfig { path: dir.path().join("download").to_string_lossy().into_owned() },
+            database: DatabaseConfig { path: dir.path().join("database") },
+            names: NamePolicyConfig { reserved: vec!["internal".to_string()], ..Default::default() },
+            dependencies: DependencyPolicyConfig { mode: DependencyPolicyMode::Warn, ..Default::default() },
+            ..Default::default()
+        };
+
+        // A registry holding foo 0.1.0 in the index, the download directory, the database and the cache
+        let (foo, foo_file) = package_and_file("foo", "");
+        let index_crate = IndexCrate::new(foo.clone(), &foo_file);
+        let index_file = config.index.path.join(index_crate.path_in_index());
+        std::fs::create_dir_all(index_file.parent().unwrap()).unwrap();
+        std::fs::write(&index_file, format!("{}\n", index_crate.index_line().unwrap())).unwrap();
+        let crate_file = Path::new(&config.download.path).join("foo/0.1.0/download");
+        std::fs::create_dir_all(crate_file.parent().unwrap()).unwrap();
+        std::fs::write(&crate_file, &foo_file).unwrap();
+        database::init(&config.database.path).unwrap();
+        let changes = database::begin_at(&config.database.path).unwrap();
+        changes.add_package(&foo, &index_crate.cksum).unwrap();
+        changes.commit().unwrap();
+        let mut cache = IndexCache::default();
+        cache.insert(index_crate);
+        let before = files_below(dir.path());
+        assert_eq!(before.len(), 3);
+
+        assert!(matches!(validate(&foo, &foo_file, "alice", &config, || &cache), Err(PublishError::VersionAlreadyExists)));
+
+        let (internal, internal_file) = package_and_file("internal", "");
+        assert!(matches!(validate(&internal, &internal_file, "alice", &config, || &cache), Err(PublishError::NamePolicy(NamePolicyError::Reserved))));
+
+        let dependency = r#"{"name":"missing","version_req":"^1","features":[],"optional":false,"default_features":true,
+            "target":null,"kind":"normal","registry":null,"explicit_name_in_toml":null}"#;
+        let (bar, bar_file) = package_and_file("bar", dependency);
+        let warnings = validate(&bar, &bar_file, "alice", &config, || &cache).unwrap();
+        assert_eq!(warnings, [DependencyPolicyError::MissingCrate("missing".to_string()).to_string()]);
+
+        assert_eq!(files_below(dir.path()), before);
+        assert_eq!(cache.versions("foo").unwrap().len(), 1);
+        assert!(cache.versions("bar").is_none());
+    }
+
+    #[test]
+    fn only_empty_parents_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("fo/o-/foo-bar");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::create_dir_all(dir.path().join("fo/ob")).unwrap();
+        remove_empty_parents(&file, dir.path());
+        assert!(!dir.path().join("fo/o-").exists());
+        assert!(dir.path().join("fo/ob").exists());
+    }
+
+    #[test]
+    fn staged_file_is_removed_unless_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("foo/0.1.0/download");
+        drop(StagedFile::write(target.clone(), b"crate").unwrap());
+        assert_eq!(std::fs::read_dir(target.parent().unwrap()).unwrap().count(), 0);
+
+        let staged = StagedFile::write(target.clone(), b"crate").unwrap();
+        assert!(!target.exists());
+        assert_eq!(staged.persist().unwrap(), target);
+        assert_eq!(std::fs::read(&target).unwrap(), b"crate");
+        assert_eq!(std::fs::read_dir(target.parent().unwrap()).unwrap().count(), 1);
+    }
+}
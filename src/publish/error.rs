@@ -5,7 +5,9 @@ use std::{error::Error,
 };
 use serde_json::error::Error as SerdeJsonError;
 
-use crate::index::error::WalkIndexError;
+use crate::{index::error::WalkIndexError, crate_file::error::CrateFileError, name_policy::error::NamePolicyError,
+    dependency::error::DependencyPolicyError, features::error::FeatureError,
+    license::error::LicenseError};
 
 #[derive(Debug)]
 pub(crate) enum PublishError{
@@ -14,6 +16,12 @@ pub(crate) enum PublishError{
     BadIndexJson,
     SerializationFailed(SerdeJsonError),
     CrateExistsWithDifferentDashUnderscore,
+    InvalidCrateFile(CrateFileError),
+    SqlError(rusqlite::Error),
+    NamePolicy(NamePolicyError),
+    DependencyPolicy(Vec<DependencyPolicyError>),
+    InvalidFeatures(Vec<FeatureError>),
+    InvalidLicense(LicenseError),
 }
 
 impl Error for PublishError {
@@ -21,6 +29,10 @@ impl Error for PublishError {
         match self {
             Self::IoError(i) => Some(i),
             Self::SerializationFailed(i) => Some(i),
+            Self::InvalidCrateFile(c) => Some(c),
+            Self::SqlError(s) => Some(s),
+            Self::NamePolicy(n) => Some(n),
+            Self::InvalidLicense(l) => Some(l),
             _ => None
         }
     }
@@ -33,7 +45,13 @@ impl Display for PublishError {
             Self::BadIndexJson => "bad index json".to_string(),
             Self::VersionAlreadyExists => "version already exists".to_string(),
             Self::SerializationFailed(e) => format!("serialization of index crate failed: {e}"),
-            Self::CrateExistsWithDifferentDashUnderscore => "crate exists with different dash/underscore name".to_string()
+            Self::CrateExistsWithDifferentDashUnderscore => "crate exists with different dash/underscore name".to_string(),
+            Self::InvalidCrateFile(e) => e.to_string(),
+            Self::SqlError(e) => format!("database access failed: {e}"),
+            Self::NamePolicy(e) => e.to_string(),
+            Self::DependencyPolicy(errors) => errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+            Self::InvalidFeatures(errors) => errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+            Self::InvalidLicense(e) => e.to_string(),
         })
     }
 }
@@ -46,6 +64,26 @@ impl From<WalkIndexError> for PublishError {
         }
     }
 }
+impl From<CrateFileError> for PublishError {
+    fn from(value: CrateFileError) -> Self {
+        Self::InvalidCrateFile(value)
+    }
+}
+impl From<LicenseError> for PublishError {
+    fn from(value: LicenseError) -> Self {
+        Self::InvalidLicense(value)
+    }
+}
+impl From<NamePolicyError> for PublishError {
+    fn from(value: NamePolicyError) -> Self {
+        Self::NamePolicy(value)
+    }
+}
+impl From<rusqlite::Error> for PublishError {
+    fn from(value: rusqlite::Error) -> Self {
+        Self::SqlError(value)
+    }
+}
 impl From<IoError> for PublishError {
     fn from(value: IoError) -> Self {
         Self::IoError(value)
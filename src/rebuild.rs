@@ -0,0 +1,185 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    path::Path,
+};
+
+use crate::{
+    config::CONFIG,
+    crate_file::{self, Manifest, error::CrateFileError},
+    database::{self, PendingChanges},
+    download::crate_file_path,
+    index::{self, IndexCrate},
+    publish::PublishedPackage,
+};
+
+/// Adds a `crates` and `versions` row for every index entry missing in the database,
+/// creating the database first if it does not exist.
+/// Versions added before the database kept checksums get their remaining metadata filled in.
+/// Descriptive metadata is taken from the `Cargo.toml` of the stored .crate file.
+pub(crate) fn run() -> Result<(), Box<dyn Error>> {
+    let database_path = &CONFIG.database.path;
+    if database_path.is_file() {
+        println!("Adding missing versions to existing database at {}", database_path.display());
+        database::migrate(database_path)?;
+    } else {
+        println!("Creating new database at configured path {}", database_path.display());
+        database::init(database_path)?;
+    }
+    let existing: HashSet<(String, String)> = database::get_all_versions()?
+        .into_iter()
+        .map(|(name, vers)| (name.to_lowercase(), vers))
+        .collect();
+
+    let incomplete: HashSet<(String, String)> = database::get_versions_without_checksum()?
+        .into_iter()
+        .map(|(name, vers)| (name.to_lowercase(), vers))
+        .collect();
+
+    let (mut added, mut completed, mut skipped) = (0, 0, 0);
+    for entry in index::walk_index_crates() {
+        let index_crate = match entry {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: {e}");
+                skipped += 1;
+                continue;
+            }
+        };
+        let key = (index_crate.name.to_lowercase(), index_crate.vers.clone());
+        let complete_existing = incomplete.contains(&key);
+        if existing.contains(&key) && !complete_existing {
+            continue;
+        }
+        let (yanked, cksum) = (index_crate.yanked, index_crate.cksum.clone());
+        let package = package_from_crate_file(index_crate);
+        let changes = database::begin()?;
+        if complete_existing {
+            changes.complete_package(&package, &cksum)?;
+            changes.commit()?;
+            completed += 1;
+            continue;
+        }
+        add_version(&changes, &package, &cksum, yanked)?;
+        changes.commit()?;
+        added += 1;
+    }
+    println!("Added {added} version(s), completed metadata of {completed}, skipped {skipped} unreadable index entries");
+    Ok(())
+}
+
+/// Adds the database rows of the version `vers` of `name` from its index entry and stored crate file
+pub(crate) fn restore_version(name: &str, vers: &str) -> Result<(), Box<dyn Error>> {
+    let path_in_index = IndexCrate { name: name.to_string(), ..Default::default() }.path_in_index();
+    let index_crate = index::walk_index_crates_with_file_predicate(|path| path.ends_with(&path_in_index))
+        .filter_map(Result::ok)
+        .find(|c| c.name.eq_ignore_ascii_case(name) && c.vers == vers)
+        .ok_or("no readable index entry")?;
+    let (yanked, cksum) = (index_crate.yanked, index_crate.cksum.clone());
+    let package = package_from_crate_file(index_crate);
+    let changes = database::begin()?;
+    add_version(&changes, &package, &cksum, yanked)?;
+    Ok(changes.commit()?)
+}
+
+fn add_version(changes: &PendingChanges, package: &PublishedPackage, cksum: &str, yanked: bool) -> Result<(), rusqlite::Error> {
+    changes.add_package(package, cksum)?;
+    if yanked {
+        changes.set_yanked(&package.name, &package.vers, true, None)?;
+    }
+    Ok(())
+}
+
+/// The package described by `index_crate` with the metadata of its stored crate file,
+/// or with name and version only if the file cannot be read
+fn package_from_crate_file(index_crate: IndexCrate) -> PublishedPackage {
+    let crate_file = std::fs::read(crate_file_path(&index_crate.name, &index_crate.vers));
+    let metadata = crate_file
+        .map_err(CrateFileError::from)
+        .and_then(|file| read_metadata(&file, &index_crate));
+    match metadata {
+        Ok((manifest, readme)) => published_package(index_crate, Some(manifest), readme),
+        Err(e) => {
+            println!("No metadata for {} v{}, adding name and version only: {e}", index_crate.name, index_crate.vers);
+            published_package(index_crate, None, None)
+        }
+    }
+}
+
+fn read_metadata(crate_file: &[u8], index_crate: &IndexCrate) -> Result<(Manifest, Option<String>), CrateFileError> {
+    let manifest = crate_file::read_manifest(crate_file, &index_crate.name, &index_crate.vers)?;
+    let readme = match manifest.package.readme_path() {
+        Some(path) => crate_file::read_file(crate_file, &index_crate.name, &index_crate.vers, Path::new(path))?
+            .map(|content| String::from_utf8_lossy(&content).into_owned()),
+        None => None,
+    };
+    Ok((manifest, readme))
+}
+
+fn published_package(index_crate: IndexCrate, manifest: Option<Manifest>, readme: Option<String>) -> PublishedPackage {
+    let IndexCrate { name, vers, deps, features, features2, links, rust_version, .. } = index_crate;
+    let mut package = PublishedPackage {
+        name,
+        vers,
+        deps: deps.into_iter().map(Into::into).collect(),
+        features: features.into_iter().chain(features2).collect(),
+        authors: vec![],
+        description: None,
+        documentation: None,
+        homepage: None,
+        readme,
+        readme_file: None,
+        keywords: vec![],
+        categories: vec![],
+        license: None,
+        license_file: None,
+        repository: None,
+        badges: HashMap::new(),
+        links,
+        rust_version,
+        unknown_fields: BTreeMap::new(),
+    };
+    if let Some(Manifest { package: manifest }) = manifest {
+        package.readme_file = manifest.readme_path().map(ToString::to_string);
+        package.authors = manifest.authors;
+        package.description = manifest.description;
+        package.documentation = manifest.documentation;
+        package.homepage = manifest.homepage;
+        package.keywords = manifest.keywords;
+        package.categories = manifest.categories;
+        package.license = manifest.license;
+        package.license_file = manifest.license_file;
+        package.repository = manifest.repository;
+        package.rust_version = package.rust_version.or(manifest.rust_version);
+    }
+    package
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{published_package, read_metadata};
+    use crate::{index::IndexCrate, crate_file::tests::build_crate_file};
+
+    #[test]
+    fn package_from_crate_file() {
+        let file = build_crate_file(&[
+            ("foo-0.1.0/Cargo.toml", b"[package]\nname = \"foo\"\nversion = \"0.1.0\"\nlicense = \"MIT\"\nreadme = \"README.md\"\n"),
+            ("foo-0.1.0/README.md", b"Hello"),
+        ]);
+        let index_crate = IndexCrate { name: "foo".to_string(), vers: "0.1.0".to_string(), ..Default::default() };
+        let (manifest, readme) = read_metadata(&file, &index_crate).unwrap();
+        let package = published_package(index_crate, Some(manifest), readme);
+        assert_eq!(package.name, "foo");
+        assert_eq!(package.license.as_deref(), Some("MIT"));
+        assert_eq!(package.readme.as_deref(), Some("Hello"));
+        assert_eq!(package.readme_file.as_deref(), Some("README.md"));
+    }
+
+    #[test]
+    fn package_without_crate_file() {
+        let index_crate = IndexCrate { name: "foo".to_string(), vers: "0.1.0".to_string(), ..Default::default() };
+        let package = published_package(index_crate, None, None);
+        assert_eq!(package.vers, "0.1.0");
+        assert_eq!(package.description, None);
+    }
+}
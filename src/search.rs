@@ -1,11 +1,11 @@
-use std::{io::{Result as IoResult, Error as IoError, ErrorKind, Write}, net::TcpStream, str::FromStr};
+use std::{io::{Result as IoResult, Write}, net::TcpStream, str::FromStr};
 
 use serde::Serialize;
 
 use crate::{
-    index::{IndexCrate, self}, 
+    index::{IndexCrate, self},
     error::ReturnJson,
-    database,
+    database, version,
     http::{Response, Byteable}
 };
 
@@ -13,56 +13,40 @@ use self::error::SearchResultError;
 
 mod error;
 
-type CrateVersions = Vec<IndexCrate>;
-
 pub fn handle_search_request(mut stream: TcpStream, path: &str) -> IoResult<()> {
     let query = match path.parse::<Query>() {
         Ok(query) => query,
         Err(e) => return stream.write_all(&Response::new(400).body(ReturnJson::new(&[e])).into_bytes())
     };
 
-    let crates = index::walk_index_crates();
-    let crate_versions: Vec<CrateVersions> = crates.fold(Ok(vec![]), |vec: Result<Vec<Vec<IndexCrate>>, IoError>, new_crate| {
-        let (mut vector, new_crate) = match (vec, new_crate) {
-            (Ok(x), Ok(y)) => (x, y),
-            (v, _)  => return v
-        };
-        let Some(last_crate_name) = vector.last()
-            .and_then(|v| v.first())
-            .map(|i| i.name.clone()) else {
-            return Ok(vec![vec![new_crate]]);
-        };
-        if last_crate_name == new_crate.name {
-            vector.last_mut().expect("should be initialized non-empty").push(new_crate);
-            Ok(vector)
-        } else {
-            vector.push(vec![new_crate]);
-            Ok(vector)
-        }
-    })?;
+    let (mut descriptions, keywords) = match database::get_all_descriptions().and_then(|d| Ok((d, database::get_all_keywords()?))) {
+        Ok(d) => d,
+        Err(e) => return stream.write_all(&Response::new(500).body(ReturnJson::new(&[e])).into_bytes())
+    };
 
     // Alle übrigen Gruppen zu Suchergebnissen umwandeln
     // TryFrom handlet auch Crates die nur Yanked versionen haben
     // Flattening all crates to search result elements
-    let crate_groups = crate_versions.into_iter()
+    let crate_groups = index::cache::all().into_iter()
         .filter_map(|u| 
-            match u.try_into() {
-                Ok(x) => Some(Ok(x)),
+            match SearchResult::try_from(u) {
+                Ok(mut x) => {
+                    x.description = descriptions.remove(&(x.name.clone(), x.max_version.clone())).unwrap_or_default();
+                    Some(x)
+                },
                 // Ein Fehler durch das Umwandeln eines leeren Vektors kann als fehlender Vektor gesehen werden
                 // An error caused by an empty Vector can be passed as "no search result"
                 Err(SearchResultError::EmptyVector) => None,
-                // Parsingfehler müssen allerdings durchgereicht werden!
-                // A parsing mistake has to be handled and will pass as a 500 here
-                Err(e) => Some(Err(IoError::new(ErrorKind::InvalidData, e))),
             }
         )
-        .collect::<Result<Vec<SearchResult>,_>>()?;
+        .collect::<Vec<SearchResult>>();
 
     let results_matching_query = crate_groups.into_iter()
         .filter(|i: &SearchResult| {
-            let desc = database::get_description(&i.name, &i.max_version).unwrap().map(|x| x.to_ascii_lowercase());
             i.name.contains(&query.query_string.replace('-',"_").to_ascii_lowercase())
-            || (desc.is_some() && desc.unwrap().contains(&query.query_string.to_ascii_lowercase()))
+            || i.description.to_ascii_lowercase().contains(&query.query_string.to_ascii_lowercase())
+            || keywords.get(&(i.name.clone(), i.max_version.clone()))
+                .is_some_and(|k| k.iter().any(|k| k.eq_ignore_ascii_case(&query.query_string)))
         })
         .collect::<Vec<_>>();
 
@@ -135,24 +119,27 @@ impl TryFrom<Vec<IndexCrate>> for SearchResult {
             };
         let max_version = value.into_iter()
             .filter(|i| !i.yanked)
-            .map(|x| {
-                let mut splittage = x.vers.split('.')
-                    .take(3)
-                    .map(str::parse)
-                .collect::<Result<Vec<_>,_>>()?
-                .into_iter();
-                Ok((
-                    splittage.next().ok_or(SearchResultError::MissingVersionElements)?,
-                    splittage.next().ok_or(SearchResultError::MissingVersionElements)?,
-                    splittage.next().ok_or(SearchResultError::MissingVersionElements)?,
-                ))
-            })
-            .collect::<Result<Vec<(u32, u32, u32)>,SearchResultError>>()?
-            .into_iter()
-            .max()
-            .map(|(a, b, c)| format!("{a}.{b}.{c}"))
+            .map(|i| i.vers)
+            .max_by(|a, b| version::compare(a, b))
             .ok_or(SearchResultError::EmptyVector)?;
-        let description = database::get_description(&name, &max_version).unwrap().unwrap_or_default();
-        Ok(SearchResult { name, max_version, description })
+        // Descriptions are looked up for all results at once
+        Ok(SearchResult { name, max_version, description: String::new() })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::SearchResult;
+    use crate::index::IndexCrate;
+
+    fn index_crate(vers: &str, yanked: bool) -> IndexCrate {
+        IndexCrate { name: "foo".to_string(), vers: vers.to_string(), yanked, ..Default::default() }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn max_version_uses_semver_order() {
+        let versions = vec![index_crate("1.0.0-beta.1", false), index_crate("0.10.0", false), index_crate("0.9.0", false)];
+        assert_eq!(SearchResult::try_from(versions).unwrap().max_version, "1.0.0-beta.1");
+        let versions = vec![index_crate("1.0.0", true), index_crate("1.0.0-rc.1", false), index_crate("1.0.0-rc.0", false)];
+        assert_eq!(SearchResult::try_from(versions).unwrap().max_version, "1.0.0-rc.1");
+    }
+}
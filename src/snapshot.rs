@@ -0,0 +1,326 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use tar::{Archive, Builder, EntryType, Header};
+use walkdir::WalkDir;
+
+use crate::{config::CONFIG, database, git, index::pubtime_now};
+
+const MANIFEST_NAME: &str = "manifest.json";
+const DATABASE_NAME: &str = "database";
+const INDEX_PREFIX: &str = "index";
+const CRATES_PREFIX: &str = "crates";
+const FORMAT_VERSION: u32 = 1;
+
+/// Writes index, database and crate files into one gzipped tarball at `target`.
+/// `manifest.json` is the last entry and lists the SHA-256 checksum of every other file.
+pub(crate) fn export(target: &Path, with_git: bool) -> Result<(), Box<dyn Error>> {
+    println!("Exporting registry to {}", target.display());
+    let file = File::create(target)?;
+    let manifest = write_snapshot(file, &Locations::configured(), with_git)?;
+    println!("Exported {} file(s)", manifest.files.len());
+    Ok(())
+}
+
+/// Replaces index, database and crate files with the content of the snapshot at `source`.
+/// Everything is unpacked and verified next to its destination before being renamed into place,
+/// the previous state is restored if any rename fails. The server must not be running.
+pub(crate) fn import(source: &Path) -> Result<(), Box<dyn Error>> {
+    println!("Importing registry from {}", source.display());
+    let manifest = restore(File::open(source)?, &Locations::configured())?;
+    database::migrate(&CONFIG.database.path)?;
+    if !manifest.with_git {
+        git::init_index()?;
+        git::add_and_commit_to_index(&".", "Import registry snapshot")?;
+    }
+    println!("Imported {} file(s) from snapshot created at {}", manifest.files.len(), manifest.created);
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Manifest {
+    format: u32,
+    created: String,
+    with_git: bool,
+    /// Archive path to SHA-256 checksum
+    files: BTreeMap<String, String>,
+}
+
+struct Locations {
+    index: PathBuf,
+    database: PathBuf,
+    crates: PathBuf,
+}
+
+impl Locations {
+    fn configured() -> Self {
+        Self {
+            index: CONFIG.index.path.clone(),
+            database: CONFIG.database.path.clone(),
+            crates: PathBuf::from(&CONFIG.download.path),
+        }
+    }
+
+    fn with_suffix(&self, suffix: &str) -> Self {
+        Self {
+            index: with_suffix(&self.index, suffix),
+            database: with_suffix(&self.database, suffix),
+            crates: with_suffix(&self.crates, suffix),
+        }
+    }
+
+    /// Where the file at `archive_path` belongs
+    fn destination(&self, archive_path: &str) -> Result<PathBuf, Box<dyn Error>> {
+        match archive_path.split_once('/') {
+            Some((INDEX_PREFIX, rest)) => Ok(self.index.join(rest)),
+            Some((CRATES_PREFIX, rest)) => Ok(self.crates.join(rest)),
+            None if archive_path == DATABASE_NAME => Ok(self.database.clone()),
+            _ => Err(format!("unexpected file {archive_path} in snapshot").into()),
+        }
+    }
+
+    fn pairs<'a>(&'a self, other: &'a Self) -> [(&'a Path, &'a Path); 3] {
+        [
+            (&self.index, &other.index),
+            (&self.database, &other.database),
+            (&self.crates, &other.crates),
+        ]
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{suffix}"));
+    path.with_file_name(name)
+}
+
+fn write_snapshot<W: Write>(writer: W, locations: &Locations, with_git: bool) -> Result<Manifest, Box<dyn Error>> {
+    let mut builder = Builder::new(GzEncoder::new(writer, Compression::default()));
+    let mut files = BTreeMap::new();
+
+    for (prefix, root) in [(INDEX_PREFIX, &locations.index), (CRATES_PREFIX, &locations.crates)] {
+        if !root.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(root).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() || (!with_git && entry.path().starts_with(root.join(".git"))) {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(root)?;
+            let name = archive_path(&Path::new(prefix).join(relative))?;
+            let content = fs::read(entry.path())?;
+            append(&mut builder, &name, &content)?;
+            files.insert(name, sha256::digest(content.as_slice()));
+        }
+    }
+
+    // A copy taken through SQLite is consistent even if the file is being written to
+    let database_copy = with_suffix(&locations.database, "export");
+    let _ = fs::remove_file(&database_copy);
+    database::backup(&locations.database, &database_copy)?;
+    let content = fs::read(&database_copy);
+    fs::remove_file(&database_copy)?;
+    let content = content?;
+    append(&mut builder, DATABASE_NAME, &content)?;
+    files.insert(DATABASE_NAME.to_string(), sha256::digest(content.as_slice()));
+
+    let manifest = Manifest { format: FORMAT_VERSION, created: pubtime_now(), with_git, files };
+    append(&mut builder, MANIFEST_NAME, &serde_json::to_vec_pretty(&manifest)?)?;
+    builder.into_inner()?.finish()?.flush()?;
+    Ok(manifest)
+}
+
+fn append<W: Write>(builder: &mut Builder<W>, name: &str, content: &[u8]) -> std::io::Result<()> {
+    let mut header = Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, content)
+}
+
+/// The path as stored in the archive, rejecting anything that could leave the destination
+fn archive_path(path: &Path) -> Result<String, Box<dyn Error>> {
+    path.components()
+        .map(|c| match c {
+            Component::Normal(part) => part.to_str().ok_or_else(|| format!("{} is not valid UTF-8", path.display()).into()),
+            _ => Err(format!("unsafe path {} in snapshot", path.display()).into()),
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()
+        .map(|parts| parts.join("/"))
+}
+
+fn restore<R: Read>(reader: R, locations: &Locations) -> Result<Manifest, Box<dyn Error>> {
+    let staging = locations.with_suffix("importing");
+    remove_all(&staging);
+    let result = unpack_and_verify(reader, &staging)
+        .and_then(|manifest| {
+            swap_into_place(&staging, locations)?;
+            Ok(manifest)
+        });
+    remove_all(&staging);
+    result
+}
+
+fn unpack_and_verify<R: Read>(reader: R, staging: &Locations) -> Result<Manifest, Box<dyn Error>> {
+    fs::create_dir_all(&staging.index)?;
+    fs::create_dir_all(&staging.crates)?;
+    let mut manifest: Option<Manifest> = None;
+    let mut checksums = BTreeMap::new();
+    let mut archive = Archive::new(GzDecoder::new(reader));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        match entry.header().entry_type() {
+            EntryType::Regular => {},
+            EntryType::Directory => continue,
+            other => return Err(format!("unsupported entry type {other:?} in snapshot").into()),
+        }
+        let name = archive_path(&entry.path()?)?;
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+        if name == MANIFEST_NAME {
+            manifest = Some(serde_json::from_slice(&content)?);
+            continue;
+        }
+        let destination = staging.destination(&name)?;
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(destination, &content)?;
+        checksums.insert(name, sha256::digest(content.as_slice()));
+    }
+
+    let manifest = manifest.ok_or("snapshot contains no manifest")?;
+    if manifest.format != FORMAT_VERSION {
+        return Err(format!("unsupported snapshot format {}", manifest.format).into());
+    }
+    if !manifest.files.contains_key(DATABASE_NAME) {
+        return Err("snapshot contains no database".into());
+    }
+    for (name, checksum) in &manifest.files {
+        match checksums.remove(name) {
+            Some(actual) if &actual == checksum => {},
+            Some(_) => return Err(format!("checksum mismatch for {name}").into()),
+            None => return Err(format!("{name} is missing in snapshot").into()),
+        }
+    }
+    if let Some(name) = checksums.keys().next() {
+        return Err(format!("{name} is not listed in the manifest").into());
+    }
+    Ok(manifest)
+}
+
+/// Moves every staged location to its target, keeping the old content until all moves succeeded
+fn swap_into_place(staging: &Locations, targets: &Locations) -> std::io::Result<()> {
+    let backups = targets.with_suffix("before-import");
+    remove_all(&backups);
+    let mut moved: Vec<(&Path, &Path, &Path)> = vec![];
+    for ((staged, target), (_, backup)) in staging.pairs(targets).into_iter().zip(targets.pairs(&backups)) {
+        let result = move_existing(target, backup).and_then(|()| fs::rename(staged, target));
+        if let Err(e) = result {
+            // Roll back, including the pair that just failed
+            let _ = move_existing(backup, target);
+            for (_, target, backup) in moved.into_iter().rev() {
+                let _ = remove(target);
+                let _ = move_existing(backup, target);
+            }
+            return Err(e);
+        }
+        moved.push((staged, target, backup));
+    }
+    remove_all(&backups);
+    Ok(())
+}
+
+fn move_existing(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.exists() {
+        fs::rename(from, to)?;
+    }
+    Ok(())
+}
+
+fn remove(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn remove_all(locations: &Locations) {
+    for path in [&locations.index, &locations.database, &locations.crates] {
+        if path.exists() {
+            let _ = remove(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+    use rusqlite::Connection;
+    use super::{Locations, write_snapshot, restore, with_suffix};
+
+    fn locations(root: &Path) -> Locations {
+        Locations { index: root.join("index"), database: root.join("database"), crates: root.join("crates") }
+    }
+
+    fn create_registry(root: &Path) -> Locations {
+        let locations = locations(root);
+        fs::create_dir_all(locations.index.join("3/f")).unwrap();
+        fs::create_dir_all(locations.index.join(".git")).unwrap();
+        fs::write(locations.index.join("3/f/foo"), "{}\n").unwrap();
+        fs::write(locations.index.join(".git/HEAD"), "ref: refs/heads/master\n").unwrap();
+        fs::create_dir_all(locations.crates.join("foo/0.1.0")).unwrap();
+        fs::write(locations.crates.join("foo/0.1.0/download"), b"crate").unwrap();
+        Connection::open(&locations.database).unwrap()
+            .execute("CREATE TABLE crates (name TEXT)", ()).unwrap();
+        locations
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let source = tempfile::tempdir().unwrap();
+        let target = tempfile::tempdir().unwrap();
+        let source = create_registry(source.path());
+        let mut archive = vec![];
+        let manifest = write_snapshot(&mut archive, &source, false).unwrap();
+        assert!(manifest.files.keys().all(|f| !f.contains(".git")));
+
+        let target = locations(target.path());
+        fs::create_dir_all(&target.index).unwrap();
+        fs::write(target.index.join("old"), "replaced").unwrap();
+        restore(archive.as_slice(), &target).unwrap();
+        assert_eq!(fs::read_to_string(target.index.join("3/f/foo")).unwrap(), "{}\n");
+        assert!(!target.index.join("old").exists());
+        assert_eq!(fs::read(target.crates.join("foo/0.1.0/download")).unwrap(), b"crate");
+        let tables: usize = Connection::open(&target.database).unwrap()
+            .query_row("SELECT count(*) FROM sqlite_master WHERE name = 'crates'", [], |r| r.get(0)).unwrap();
+        assert_eq!(tables, 1);
+        assert!(!with_suffix(&target.index, "importing").exists());
+        assert!(!with_suffix(&target.index, "before-import").exists());
+    }
+
+    #[test]
+    fn corrupted_snapshot_leaves_target_untouched() {
+        let source = tempfile::tempdir().unwrap();
+        let target = tempfile::tempdir().unwrap();
+        let source = create_registry(source.path());
+        let mut archive = vec![];
+        write_snapshot(&mut archive, &source, true).unwrap();
+        archive.truncate(archive.len() / 2);
+
+        let target = create_registry(target.path());
+        fs::write(target.index.join("marker"), "still here").unwrap();
+        assert!(restore(archive.as_slice(), &target).is_err());
+        assert_eq!(fs::read_to_string(target.index.join("marker")).unwrap(), "still here");
+        assert!(!with_suffix(&target.index, "importing").exists());
+    }
+}
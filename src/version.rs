@@ -0,0 +1,43 @@
+use std::cmp::Ordering;
+
+use semver::Version;
+
+/// Orders version strings by semver precedence, including pre-releases.
+/// Versions that do not parse, which only old index entries can have, sort before all others.
+pub(crate) fn compare(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp_precedence(&b).then_with(|| a.build.cmp(&b.build)),
+        (Ok(_), Err(_)) => Ordering::Greater,
+        (Err(_), Ok(_)) => Ordering::Less,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// Whether both versions only differ in build metadata, which cargo ignores,
+/// so they cannot both be published
+pub(crate) fn same_release(a: &str, b: &str) -> bool {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp_precedence(&b) == Ordering::Equal,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use super::{compare, same_release};
+
+    #[test]
+    fn pre_releases_sort_before_release() {
+        let mut versions = vec!["1.0.0", "1.0.0-beta.11", "0.9.10", "1.0.0-beta.2", "0.9.9", "1.0.0-alpha"];
+        versions.sort_by(|a, b| compare(a, b));
+        assert_eq!(versions, ["0.9.9", "0.9.10", "1.0.0-alpha", "1.0.0-beta.2", "1.0.0-beta.11", "1.0.0"]);
+        assert_eq!(compare("not.a.version", "0.0.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn build_metadata_is_the_same_release() {
+        assert!(same_release("1.0.0+build.1", "1.0.0"));
+        assert!(!same_release("1.0.0-rc.1", "1.0.0"));
+    }
+}
@@ -0,0 +1,108 @@
+use std::{io::{Write, Result as IoResult}, net::TcpStream, fmt::Write as FmtWrite};
+
+use crate::{
+    database, version,
+    crate_info::VersionResult,
+    error::ReturnJson,
+    http::{Response, Byteable},
+};
+
+/// A plain HTML page listing all crates and their versions, including why versions were yanked
+pub fn listing(mut stream: TcpStream) -> IoResult<()> {
+    let versions = match database::get_version_results(None) {
+        Ok(v) => v,
+        Err(e) => return stream.write_all(&Response::new(500).body(ReturnJson::new(&[e])).into_bytes()),
+    };
+    let response = Response::new(200)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(render_listing(&versions));
+    stream.write_all(&response.into_bytes())
+}
+
+/// Crates are listed by name, the versions of each in semver order
+fn render_listing(versions: &[VersionResult]) -> String {
+    let mut versions: Vec<_> = versions.iter().collect();
+    versions.sort_by(|a, b| a.crate_name.cmp(&b.crate_name).then_with(|| version::compare(&a.num, &b.num)));
+    let mut html = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Crates</title></head><body>\n<h1>Crates</h1>\n");
+    let mut current_crate = None;
+    for version in versions {
+        if current_crate != Some(&version.crate_name) {
+            if current_crate.is_some() {
+                html.push_str("</ul>\n");
+            }
+            let _ = write!(html, "<h2>{}</h2>\n<ul>\n", escape(&version.crate_name));
+            current_crate = Some(&version.crate_name);
+        }
+        let _ = write!(html, "<li>{}", escape(&version.num));
+        if let Some(description) = &version.description {
+            let _ = write!(html, " &ndash; {}", escape(description));
+        }
+        if version.yanked {
+            html.push_str(" <strong>yanked</strong>");
+            if let Some(reason) = &version.yank_reason {
+                let _ = write!(html, ": {}", escape(reason));
+            }
+            match (&version.yanked_by, &version.yanked_at) {
+                (Some(by), Some(at)) => { let _ = write!(html, " ({} at {})", escape(by), escape(at)); },
+                (None, Some(at)) => { let _ = write!(html, " (at {})", escape(at)); },
+                _ => {},
+            }
+        }
+        html.push_str("</li>\n");
+    }
+    if current_crate.is_some() {
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_listing;
+    use crate::crate_info::VersionResult;
+
+    fn version(crate_name: &str, num: &str, yank_reason: Option<&str>) -> VersionResult {
+        VersionResult {
+            crate_name: crate_name.to_string(),
+            num: num.to_string(),
+            description: None,
+            license: None,
+            rust_version: None,
+            yanked: yank_reason.is_some(),
+            yank_reason: yank_reason.map(ToString::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn listing_groups_crates_and_escapes_reasons() {
+        let html = render_listing(&[
+            version("bar", "0.1.0", None),
+            version("foo", "0.1.0", Some("<script>")),
+            version("foo", "0.2.0", None),
+        ]);
+        assert_eq!(html.matches("<h2>").count(), 2);
+        assert!(html.contains("<li>0.1.0 <strong>yanked</strong>: &lt;script&gt;</li>"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn listing_orders_versions_by_semver() {
+        let html = render_listing(&[
+            version("foo", "0.10.0", None),
+            version("foo", "0.2.0", None),
+            version("foo", "0.2.0-beta.1", None),
+        ]);
+        let position = |num: &str| html.find(&format!("<li>{num}</li>")).unwrap();
+        assert!(position("0.2.0-beta.1") < position("0.2.0"));
+        assert!(position("0.2.0") < position("0.10.0"));
+    }
+}
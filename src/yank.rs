@@ -1,58 +1,235 @@
 use std::{
     net::TcpStream,
-    fs::OpenOptions,
-    io::{Write, Result as IoResult}
+    collections::HashMap,
+    time::Duration,
+    io::{Read, Write, Result as IoResult, ErrorKind},
 };
 
+use serde::Deserialize;
+
 mod error;
 
 use crate::{
     git::add_and_commit_to_index,
-    index::IndexCrate,
+    index::{self, IndexCrate},
     config::CONFIG,
-    http::{Response, Byteable}
+    database,
+    error::ReturnJson,
+    index::pubtime_now,
+    http::{Response, Byteable, NeedsMessage}
 };
 
-pub(crate) fn unyank(stream: TcpStream, crate_name: &str, version: &str, auth: &str) -> IoResult<()>{
-    replace_yanked_field(stream, crate_name, version, auth, false)
+use self::error::YankError;
+
+/// Longest accepted yank reason in characters
+const MAX_REASON_LENGTH: usize = 1000;
+/// Largest accepted request body, enough for the longest reason in UTF-8
+const MAX_BODY_LENGTH: usize = 4 * MAX_REASON_LENGTH;
+/// How long a client may take to send the announced reason
+const BODY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Who yanked a version when and why, as stored in the database
+#[derive(Debug)]
+pub(crate) struct YankMetadata {
+    pub(crate) reason: Option<String>,
+    /// The configured name of the user, never the token itself
+    pub(crate) actor: Option<String>,
+    pub(crate) time: String,
 }
 
-pub(crate) fn yank(stream: TcpStream, crate_name: &str, version: &str, auth: &str) -> IoResult<()> {
-    replace_yanked_field(stream, crate_name, version, auth, true)
+pub(crate) fn unyank(mut stream: TcpStream, crate_name: &str, version: &str, auth: &str) -> IoResult<()>{
+    println!("UNYANK {crate_name} v{version} [{auth}]");
+    let response = match set_yanked(crate_name, version, None) {
+        Ok(()) => Response::new(200).body(r#"{"ok":true}"#),
+        Err(e) => error_response(e),
+    };
+    stream.write_all(&response.into_bytes())
 }
 
-fn replace_yanked_field(mut stream: TcpStream, crate_name: &str, version: &str, auth: &str, yanked: bool) -> IoResult<()> {
-    println!("{} {crate_name} v{version} [{auth}]", 
-        if yanked {"YANK"} else {"UNYANK"});
+/// Cargo sends no body, other clients can give a reason as plain text or as `{"reason": "..."}`
+pub(crate) fn yank(mut stream: TcpStream, crate_name: &str, version: &str, headers: &HashMap<String, String>, auth: &str) -> IoResult<()> {
+    let reason = match read_reason(&mut stream, headers) {
+        Ok(reason) => reason,
+        Err(YankError::IoError(e)) => return Err(e),
+        Err(e) => return stream.write_all(&error_response(e).into_bytes()),
+    };
+    println!("YANK {crate_name} v{version} [{auth}]{}", reason.as_ref().map(|r| format!(": {r}")).unwrap_or_default());
+    let metadata = YankMetadata { reason, actor: CONFIG.users.get(auth).cloned(), time: pubtime_now() };
+    let response = match set_yanked(crate_name, version, Some(&metadata)) {
+        Ok(()) => Response::new(200).body(r#"{"ok":true}"#),
+        Err(e) => error_response(e),
+    };
+    stream.write_all(&response.into_bytes())
+}
+
+fn error_response(e: YankError) -> Response<NeedsMessage> {
+    let code = match e {
+        YankError::CrateNotFound | YankError::VersionNotFound => 404,
+        YankError::InvalidReason(_) => 400,
+        YankError::IoError(_) | YankError::BadIndexJson(_, _) | YankError::SqlError(_) => 500,
+    };
+    Response::new(code).body(ReturnJson::new(&[e]))
+}
+
+fn read_reason(stream: &mut TcpStream, headers: &HashMap<String, String>) -> Result<Option<String>, YankError> {
+    let length = body_length(headers)?;
+    if length == 0 {
+        return Ok(None);
+    }
+    stream.write_all(&Response::new(100).into_bytes())?;
+    let mut body = vec![0; length];
+    stream.set_read_timeout(Some(BODY_TIMEOUT))?;
+    match stream.read_exact(&mut body) {
+        Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::UnexpectedEof) => {
+            return Err(YankError::InvalidReason("reason is shorter than Content-Length"));
+        },
+        result => result?,
+    }
+    let body = String::from_utf8(body).map_err(|_| YankError::InvalidReason("reason is not valid UTF-8"))?;
+    parse_reason(&body)
+}
+
+/// Reads Content-Length, rejecting bodies too large for a reason before anything is allocated
+fn body_length(headers: &HashMap<String, String>) -> Result<usize, YankError> {
+    let length: usize = match headers.get("Content-Length") {
+        None => return Ok(0),
+        Some(length) => length.parse().map_err(|_| YankError::InvalidReason("Content-Length is not a number"))?,
+    };
+    if length > MAX_BODY_LENGTH {
+        return Err(YankError::InvalidReason("reason is too long"));
+    }
+    Ok(length)
+}
+
+fn parse_reason(body: &str) -> Result<Option<String>, YankError> {
+    #[derive(Deserialize)]
+    struct ReasonJson {
+        reason: Option<String>,
+    }
+    let reason = match serde_json::from_str::<ReasonJson>(body) {
+        Ok(json) => json.reason,
+        Err(_) => Some(body.to_string()),
+    };
+    let reason = reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+    if reason.as_ref().is_some_and(|r| r.chars().count() > MAX_REASON_LENGTH) {
+        return Err(YankError::InvalidReason("reason is too long"));
+    }
+    Ok(reason)
+}
 
+/// Yanks the version if there is `metadata`, unyanks it otherwise.
+/// Index and database either both change or neither does.
+fn set_yanked(crate_name: &str, version: &str, metadata: Option<&YankMetadata>) -> Result<(), YankError> {
+    let yanked = metadata.is_some();
+    let _index_lock = index::WRITE_LOCK.lock().expect("index writer panicked");
     let index_file_path_relative = IndexCrate {name: crate_name.to_string(), ..Default::default()}.path_in_index();
     let index_file_path_absolute = &CONFIG.index.path.join(&index_file_path_relative);
 
-    let content = std::fs::read_to_string(index_file_path_absolute)?;
-    let mut new_file_content = content.lines().map(ToString::to_string).collect::<Vec<_>>();
-    let index: Option<usize> = new_file_content.iter()
+    let content = match std::fs::read_to_string(index_file_path_absolute) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Err(YankError::CrateNotFound),
+        content => content?,
+    };
+    // Rolled back when dropped without commit
+    let changes = database::begin()?;
+    changes.set_yanked(&crate_name.to_lowercase(), version, yanked, metadata)?;
+    let Some((new_content, name)) = set_yanked_in_file_content(&content, version, yanked)? else {
+        return Ok(changes.commit()?);
+    };
+    index::write_file_atomically(index_file_path_absolute, new_content.as_bytes())?;
+    let message = format!("{} package [{}] version [{}] from index", if yanked {"Yank"} else {"Unyank"}, name, version);
+    if let Err(e) = add_and_commit_to_index(&index_file_path_relative, &message) {
+        index::write_file_atomically(index_file_path_absolute, content.as_bytes())?;
+        return Err(e.into());
+    }
+    if let Err(e) = changes.commit() {
+        // The commit is already in the history and maybe pushed, so it is reverted by another one
+        index::write_file_atomically(index_file_path_absolute, content.as_bytes())?;
+        add_and_commit_to_index(&index_file_path_relative, &format!("Revert: {message}"))?;
+        return Err(e.into());
+    }
+    index::cache::set_yanked(&name, version, yanked);
+    Ok(())
+}
+
+/// Returns the new file content and the crate name as written in the index,
+/// or `None` if the version already has the requested yanked state
+fn set_yanked_in_file_content(content: &str, version: &str, yanked: bool) -> Result<Option<(String, String)>, YankError> {
+    let mut crates = content.lines()
         .enumerate()
-        .find_map(|(i, x)| {
-            let icrate = serde_json::from_str::<IndexCrate>(x).ok()?;
-            (icrate.vers == version).then_some(i)
-        });
-    
-    if let Some(index) = index {
-        new_file_content[index] = new_file_content.get(index).expect("just parsed, cannot be empty").replace(
-            &format!("\"yanked\":{}", !yanked), 
-            &format!("\"yanked\":{yanked}"));
-    };
-
-    let mut index_file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .open(index_file_path_absolute)?;
-
-    write!(index_file, "{}\r\n", new_file_content.join("\r\n"))?;
-    drop(index_file);
-
-    add_and_commit_to_index(&index_file_path_relative, &format!("{} package [{}] version [{}] from index", 
-        if yanked {"Yank"} else {"Unyank"}, crate_name, version))?;
-    
-    stream.write_all(&Response::new(200).body(r#"{"ok":true}"#).into_bytes())
-}
\ No newline at end of file
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str::<IndexCrate>(line).map_err(|e| YankError::BadIndexJson(e, i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let entry = crates.iter_mut()
+        .find(|c| c.vers == version)
+        .ok_or(YankError::VersionNotFound)?;
+    if entry.yanked == yanked {
+        return Ok(None);
+    }
+    entry.yanked = yanked;
+    let name = entry.name.clone();
+    let new_content = index::index_file_content(&crates)
+        .map_err(|e| YankError::BadIndexJson(e, 0))?;
+    Ok(Some((new_content, name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{set_yanked_in_file_content, parse_reason, body_length, error::YankError};
+
+    const FILE: &str = concat!(
+        r#"{"name":"foo","vers":"0.1.0","deps":[],"cksum":"abc","features":{},"yanked":false,"v":1}"#, "\r\n",
+        r#"{"name":"foo","vers":"0.2.0","deps":[],"cksum":"def","features":{},"yanked":false,"v":1}"#, "\r\n",
+    );
+
+    #[test]
+    fn yank_only_changes_version() {
+        let (content, name) = set_yanked_in_file_content(FILE, "0.2.0", true).unwrap().unwrap();
+        assert_eq!(name, "foo");
+        let lines: Vec<_> = content.lines().collect();
+        assert!(lines[0].contains(r#""vers":"0.1.0""#) && lines[0].contains(r#""yanked":false"#));
+        assert!(lines[1].contains(r#""vers":"0.2.0""#) && lines[1].contains(r#""yanked":true"#));
+    }
+
+    #[test]
+    fn yank_keeps_unknown_fields() {
+        let file = r#"{"name":"foo","vers":"0.1.0","deps":[],"cksum":"abc","features":{},"yanked":false,"v":1,"future":"kept"}"#;
+        let (content, _) = set_yanked_in_file_content(file, "0.1.0", true).unwrap().unwrap();
+        assert!(content.contains(r#""yanked":true"#) && content.contains(r#""future":"kept""#));
+    }
+
+    #[test]
+    fn unchanged_state_writes_nothing() {
+        assert!(set_yanked_in_file_content(FILE, "0.1.0", false).unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_version() {
+        assert!(matches!(set_yanked_in_file_content(FILE, "1.0.0", true), Err(YankError::VersionNotFound)));
+    }
+
+    #[test]
+    fn reason_from_json_or_text() {
+        assert_eq!(parse_reason(r#"{"reason":"security issue"}"#).unwrap().as_deref(), Some("security issue"));
+        assert_eq!(parse_reason("broken build\n").unwrap().as_deref(), Some("broken build"));
+        assert_eq!(parse_reason(r#"{"reason":null}"#).unwrap(), None);
+        assert_eq!(parse_reason("  ").unwrap(), None);
+        assert!(matches!(parse_reason(&"x".repeat(1001)), Err(YankError::InvalidReason(_))));
+    }
+
+    #[test]
+    fn body_length_is_capped() {
+        let headers = |length: &str| HashMap::from([("Content-Length".to_string(), length.to_string())]);
+        assert_eq!(body_length(&HashMap::new()).unwrap(), 0);
+        assert_eq!(body_length(&headers("4000")).unwrap(), 4000);
+        assert!(matches!(body_length(&headers("4001")), Err(YankError::InvalidReason(_))));
+        assert!(matches!(body_length(&headers("18446744073709551615")), Err(YankError::InvalidReason(_))));
+        assert!(matches!(body_length(&headers("-1")), Err(YankError::InvalidReason(_))));
+    }
+
+    #[test]
+    fn unparsable_line() {
+        assert!(matches!(set_yanked_in_file_content("{}\n", "0.1.0", true), Err(YankError::BadIndexJson(_, 0))));
+    }
+}
@@ -4,25 +4,49 @@ use std::{
     io::Error as IoError,
 };
 
+use serde_json::error::Error as SerdeJsonError;
+
 #[derive(Debug)]
+#[allow(clippy::module_name_repetitions)]
 pub(crate) enum YankError {
-    ConnectionClosed(IoError),
+    IoError(IoError),
+    CrateNotFound,
+    VersionNotFound,
+    BadIndexJson(SerdeJsonError, usize),
+    SqlError(rusqlite::Error),
+    InvalidReason(&'static str),
 }
 impl Error for YankError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
-            Self::ConnectionClosed(i) => Some(i)
+            Self::IoError(i) => Some(i),
+            Self::BadIndexJson(j, _) => Some(j),
+            Self::SqlError(s) => Some(s),
+            Self::CrateNotFound | Self::VersionNotFound | Self::InvalidReason(_) => None,
         }
     }
 }
 impl Display for YankError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FMTResult {
-        write!(f, "failed to (un)yank")
+        write!(f, "failed to (un)yank: {}", match self {
+            Self::IoError(e) => e.to_string(),
+            Self::CrateNotFound => "crate does not exist".to_string(),
+            Self::VersionNotFound => "version does not exist".to_string(),
+            Self::BadIndexJson(e, line) => format!("bad index json at line {line}: {e}"),
+            Self::SqlError(e) => format!("database access failed: {e}"),
+            Self::InvalidReason(r) => format!("invalid yank reason: {r}"),
+        })
     }
 }
 
 impl From<IoError> for YankError {
     fn from(value: IoError) -> Self {
-        Self::ConnectionClosed(value)
+        Self::IoError(value)
+    }
+}
+
+impl From<rusqlite::Error> for YankError {
+    fn from(value: rusqlite::Error) -> Self {
+        Self::SqlError(value)
     }
 }